@@ -0,0 +1,162 @@
+use std::{borrow::Cow, ops::RangeInclusive};
+
+use crate::range_spec::{self, SliceError};
+
+// 書き込み可能なview。読むだけなら元のバッファを借用したまま、
+// 最初に書き込んだ時にそのviewの範囲だけをコピーして自前で持つ。
+#[derive(Debug, Clone)]
+pub struct CowView<'a, T: Clone> {
+    range: RangeInclusive<usize>,
+    dat: Cow<'a, [T]>,
+}
+
+impl<'a, T: Clone> CowView<'a, T> {
+    pub fn range(&self) -> &RangeInclusive<usize> {
+        &self.range
+    }
+
+    pub fn get(&self) -> &[T] {
+        &self.dat
+    }
+
+    // 長さは変えられない (materializeで元の範囲に書き戻すため)
+    pub fn get_mut(&mut self) -> &mut [T] {
+        self.dat.to_mut()
+    }
+
+    pub fn is_modified(&self) -> bool {
+        matches!(self.dat, Cow::Owned(_))
+    }
+}
+
+// 共有バッファに対するCoWなviewの集まり。
+// 書き込んでも元のバッファや他のviewには影響せず、materializeで編集を反映した新しいバッファを作る。
+// 範囲が重なる要素は、最後に値を書き換えたviewのものになる。
+// 書き換えはget_mutで渡した時点の中身との差で判定する (次のget_mutかmaterializeの時)。
+// そのため、同じ値を書き込んだだけの要素は書き換えとみなさない。
+#[derive(Debug, Clone)]
+pub struct CowSliceArray<'a, T: Clone> {
+    src: &'a [T],
+    views: Vec<CowView<'a, T>>,
+    // 元のバッファの各要素を最後に書き換えたviewの添字
+    writer: Vec<Option<usize>>,
+    // get_mutで渡したviewの添字と、渡した時点の中身
+    pending: Option<(usize, Vec<T>)>,
+}
+
+impl<'a, T: Clone + PartialEq> CowSliceArray<'a, T> {
+    // 切り出せない範囲があればその理由を返す
+    pub fn new(src: &'a [T], ranges: Vec<RangeInclusive<usize>>) -> Result<Self, SliceError> {
+        let views = ranges.into_iter()
+            .map(|range| {
+                let dat = Cow::Borrowed(range_spec::resolve(src, &range)?);
+                Ok(CowView { range, dat })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(CowSliceArray { src, views, writer: vec![None; src.len()], pending: None })
+    }
+
+    pub fn len(&self) -> usize {
+        self.views.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.views.is_empty()
+    }
+
+    pub fn view(&self, idx: usize) -> Option<&CowView<'a, T>> {
+        self.views.get(idx)
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&[T]> {
+        self.views.get(idx).map(|v| v.get())
+    }
+
+    // 初回はviewの範囲だけコピーされる
+    pub fn get_mut(&mut self, idx: usize) -> Option<&mut [T]> {
+        if idx >= self.views.len() {
+            return None;
+        }
+        // 前に渡したviewへの書き込みはここで確定する
+        if let Some((prev, before)) = self.pending.take() {
+            let mut writer = std::mem::take(&mut self.writer);
+            self.record(&mut writer, prev, &before);
+            self.writer = writer;
+        }
+        self.pending = Some((idx, self.views[idx].get().to_vec()));
+        Some(self.views[idx].get_mut())
+    }
+
+    // 元のバッファのコピーに、各要素を最後に書き換えたviewの値を入れる
+    pub fn materialize(&self) -> Vec<T> {
+        let mut writer = self.writer.clone();
+        if let Some((prev, before)) = &self.pending {
+            self.record(&mut writer, *prev, before);
+        }
+        let mut dat = self.src.to_vec();
+        for (i, w) in writer.iter().enumerate() {
+            if let Some(idx) = w {
+                let v = &self.views[*idx];
+                dat[i] = v.get()[i - v.range.start()].clone();
+            }
+        }
+        dat
+    }
+
+    // viewの中身がbeforeと違う要素を、そのviewが書き換えたものとする
+    fn record(&self, writer: &mut [Option<usize>], idx: usize, before: &[T]) {
+        let v = &self.views[idx];
+        for (k, (now, was)) in v.get().iter().zip(before).enumerate() {
+            if now != was {
+                writer[v.range.start() + k] = Some(idx);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cow_slice::*;
+
+    #[test]
+    // 目的：書き込むまでは元のバッファを借用し、書き込んだviewだけがコピーされるかを確認する
+    fn test_cow_slice_copy_on_write() {
+        let src = vec![1u8, 2, 3, 4, 5, 6];
+        let mut ca = CowSliceArray::new(&src, vec![0..=1, 2..=4]).unwrap();
+        assert!(!ca.view(0).unwrap().is_modified());
+        assert_eq!(ca.get(1).unwrap().as_ptr(), src[2..].as_ptr());
+
+        ca.get_mut(1).unwrap()[0] = 30;
+        assert!(ca.view(1).unwrap().is_modified());
+        assert!(!ca.view(0).unwrap().is_modified());
+        assert_eq!(ca.get(1), Some(&[30, 4, 5][..]));
+        assert_eq!(src, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(ca.materialize(), vec![1, 2, 30, 4, 5, 6]);
+    }
+
+    #[test]
+    // 目的：重なるviewは要素ごとに最後に書き換えたものが反映されるかを確認する
+    fn test_cow_slice_materialize_order() {
+        let src = [0u8; 4];
+        let mut ca = CowSliceArray::new(&src, vec![0..=2, 1..=3]).unwrap();
+        ca.get_mut(0).unwrap().fill(1);
+        ca.get_mut(1).unwrap().fill(2);
+        assert_eq!(ca.materialize(), vec![1, 2, 2, 2]);
+        ca.get_mut(0).unwrap()[2] = 3;
+        assert_eq!(ca.materialize(), vec![1, 2, 3, 2]);
+        // 書き込まずにget_mutしただけでは他のviewの書き換えを消さない
+        ca.get_mut(1).unwrap();
+        ca.get_mut(0).unwrap();
+        assert_eq!(ca.materialize(), vec![1, 2, 3, 2]);
+        ca.get_mut(1).unwrap()[1] = 4;
+        assert_eq!(ca.materialize(), vec![1, 2, 4, 2]);
+    }
+
+    #[test]
+    // 目的：切り出せない範囲があればエラーになるかを確認する
+    fn test_cow_slice_bad_range() {
+        let src = [0u8; 4];
+        assert_eq!(CowSliceArray::new(&src, vec![0..=1, 3..=4]).unwrap_err(),
+            SliceError::PastEnd { range: 3..=4, len: 4 });
+    }
+}
@@ -0,0 +1,278 @@
+use thiserror::Error;
+
+// 借用したバイト列を先頭から読み進めるカーソル。
+// 読み出したsliceは元のバッファを指すのでコピーは発生しない。
+// エラーには元のバッファ先頭からのオフセットが付く (sub_cursorで切り出した場合も通しのオフセット)。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum CursorError {
+    #[error("unexpected end of data at offset {offset}: needed {needed} bytes, {available} available")]
+    UnexpectedEof { offset: usize, needed: usize, available: usize },
+    #[error("varint at offset {offset} overflows 64 bits")]
+    VarintOverflow { offset: usize },
+    #[error("length {len} at offset {offset} does not fit in memory")]
+    LengthOverflow { offset: usize, len: u64 },
+    #[error("unsupported length prefix width {width} at offset {offset}")]
+    UnsupportedWidth { offset: usize, width: usize },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ByteCursor<'a> {
+    dat: &'a [u8],
+    pos: usize,
+    // datが元のバッファのどこから始まっているか (エラー表示用)
+    base: usize,
+}
+
+impl<'a> ByteCursor<'a> {
+    pub fn new(dat: &'a [u8]) -> Self {
+        ByteCursor { dat, pos: 0, base: 0 }
+    }
+
+    // datが元のバッファのbase番目から始まる場合。エラーのオフセットは元のバッファ基準になる
+    pub fn with_base(dat: &'a [u8], base: usize) -> Self {
+        ByteCursor { dat, pos: 0, base }
+    }
+
+    // 元のバッファ先頭からの現在位置
+    pub fn offset(&self) -> usize {
+        self.base + self.pos
+    }
+
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.dat[self.pos..]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.dat.len()
+    }
+
+    pub fn skip(&mut self, n: usize) -> Result<(), CursorError> {
+        self.read_bytes(n).map(|_| ())
+    }
+
+    pub fn peek_u8(&self) -> Result<u8, CursorError> {
+        self.remaining().first().copied().ok_or(self.eof(1))
+    }
+
+    pub fn read_bytes(&mut self, n: usize) -> Result<&'a [u8], CursorError> {
+        let rest = self.remaining();
+        if rest.len() < n {
+            return Err(self.eof(n));
+        }
+        self.pos += n;
+        Ok(&rest[..n])
+    }
+
+    pub fn read_array<const N: usize>(&mut self) -> Result<[u8; N], CursorError> {
+        let mut a = [0u8; N];
+        a.copy_from_slice(self.read_bytes(N)?);
+        Ok(a)
+    }
+
+    // n バイト分を別のカーソルとして切り出す
+    pub fn sub_cursor(&mut self, n: usize) -> Result<ByteCursor<'a>, CursorError> {
+        let base = self.offset();
+        let dat = self.read_bytes(n)?;
+        Ok(ByteCursor { dat, pos: 0, base })
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, CursorError> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    pub fn read_i8(&mut self) -> Result<i8, CursorError> {
+        Ok(self.read_u8()? as i8)
+    }
+
+    pub fn read_u16(&mut self, endian: Endian) -> Result<u16, CursorError> {
+        let a = self.read_array()?;
+        Ok(match endian {
+            Endian::Little => u16::from_le_bytes(a),
+            Endian::Big => u16::from_be_bytes(a),
+        })
+    }
+
+    pub fn read_u32(&mut self, endian: Endian) -> Result<u32, CursorError> {
+        let a = self.read_array()?;
+        Ok(match endian {
+            Endian::Little => u32::from_le_bytes(a),
+            Endian::Big => u32::from_be_bytes(a),
+        })
+    }
+
+    pub fn read_u64(&mut self, endian: Endian) -> Result<u64, CursorError> {
+        let a = self.read_array()?;
+        Ok(match endian {
+            Endian::Little => u64::from_le_bytes(a),
+            Endian::Big => u64::from_be_bytes(a),
+        })
+    }
+
+    pub fn read_i16(&mut self, endian: Endian) -> Result<i16, CursorError> {
+        Ok(self.read_u16(endian)? as i16)
+    }
+
+    pub fn read_i32(&mut self, endian: Endian) -> Result<i32, CursorError> {
+        Ok(self.read_u32(endian)? as i32)
+    }
+
+    pub fn read_i64(&mut self, endian: Endian) -> Result<i64, CursorError> {
+        Ok(self.read_u64(endian)? as i64)
+    }
+
+    // 符号なしLEB128
+    pub fn read_uleb128(&mut self) -> Result<u64, CursorError> {
+        let start = self.offset();
+        let mut v = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.read_u8()?;
+            let bits = (b & 0x7f) as u64;
+            if shift >= 64 || (shift == 63 && bits > 1) {
+                return Err(CursorError::VarintOverflow { offset: start });
+            }
+            v |= bits << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+    }
+
+    // 符号付きLEB128
+    pub fn read_sleb128(&mut self) -> Result<i64, CursorError> {
+        let start = self.offset();
+        let mut v = 0i64;
+        let mut shift = 0;
+        loop {
+            let b = self.read_u8()?;
+            // 10バイト目はbit 63だけが意味を持ち、残りはその符号拡張でなければならない
+            if shift >= 64 || (shift == 63 && !matches!(b & 0x7f, 0x00 | 0x7f)) {
+                return Err(CursorError::VarintOverflow { offset: start });
+            }
+            v |= ((b & 0x7f) as i64) << shift;
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {
+                    v |= -1i64 << shift;
+                }
+                return Ok(v);
+            }
+        }
+    }
+
+    // 長さ(ULEB128)の後に続くバイト列
+    pub fn read_prefixed_bytes(&mut self) -> Result<&'a [u8], CursorError> {
+        let offset = self.offset();
+        let len = self.read_uleb128()?;
+        let len = usize::try_from(len)
+            .map_err(|_| CursorError::LengthOverflow { offset, len })?;
+        self.read_bytes(len)
+    }
+
+    // 固定幅の長さ(u8/u16/u32/u64)の後に続くバイト列
+    pub fn read_prefixed_bytes_with(&mut self, width: usize, endian: Endian) -> Result<&'a [u8], CursorError> {
+        let offset = self.offset();
+        let len = match width {
+            1 => self.read_u8()? as u64,
+            2 => self.read_u16(endian)? as u64,
+            4 => self.read_u32(endian)? as u64,
+            8 => self.read_u64(endian)?,
+            _ => return Err(CursorError::UnsupportedWidth { offset, width }),
+        };
+        let len = usize::try_from(len)
+            .map_err(|_| CursorError::LengthOverflow { offset, len })?;
+        self.read_bytes(len)
+    }
+
+    fn eof(&self, needed: usize) -> CursorError {
+        CursorError::UnexpectedEof {
+            offset: self.offset(),
+            needed,
+            available: self.dat.len() - self.pos,
+        }
+    }
+}
+
+impl<'a> From<&'a [u8]> for ByteCursor<'a> {
+    fn from(dat: &'a [u8]) -> Self {
+        ByteCursor::new(dat)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cursor::*;
+
+    #[test]
+    // 目的：固定幅の整数が両方のエンディアンで読めるかを確認する
+    fn test_cursor_fixed_width() {
+        let dat = [0x01, 0x02, 0x01, 0x02, 0xff, 0xff, 0xff, 0xfe, 0x7f];
+        let mut c = ByteCursor::new(&dat);
+        assert_eq!(c.read_u16(Endian::Little), Ok(0x0201));
+        assert_eq!(c.read_u16(Endian::Big), Ok(0x0102));
+        assert_eq!(c.read_i32(Endian::Big), Ok(-2));
+        assert_eq!(c.read_u8(), Ok(0x7f));
+        assert!(c.is_empty());
+        assert_eq!(c.read_u8(), Err(CursorError::UnexpectedEof { offset: 9, needed: 1, available: 0 }));
+    }
+
+    #[test]
+    // 目的：LEB128が読めて、長すぎる場合はエラーになるかを確認する
+    fn test_cursor_leb128() {
+        let dat = [0xe5, 0x8e, 0x26, 0x7f, 0xc0, 0xbb, 0x78];
+        let mut c = ByteCursor::new(&dat);
+        assert_eq!(c.read_uleb128(), Ok(624485));
+        assert_eq!(c.read_sleb128(), Ok(-1));
+        assert_eq!(c.read_sleb128(), Ok(-123456));
+
+        let dat = [0xff; 11];
+        assert_eq!(ByteCursor::new(&dat).read_uleb128(), Err(CursorError::VarintOverflow { offset: 0 }));
+
+        // 10バイトの最小値・最大値は読め、10バイト目にbit 63を超えるビットがあればエラー
+        let mut dat = [0x80; 10];
+        dat[9] = 0x7f;
+        assert_eq!(ByteCursor::new(&dat).read_sleb128(), Ok(i64::MIN));
+        let mut dat = [0xff; 10];
+        dat[9] = 0x00;
+        assert_eq!(ByteCursor::new(&dat).read_sleb128(), Ok(i64::MAX));
+        for last in [0x01, 0x3f, 0x40, 0x7e] {
+            dat[9] = last;
+            assert_eq!(ByteCursor::new(&dat).read_sleb128(), Err(CursorError::VarintOverflow { offset: 0 }));
+        }
+    }
+
+    #[test]
+    // 目的：長さ付きバイト列と切り出したカーソルが元のバッファを指し、通しのオフセットでエラーになるかを確認する
+    fn test_cursor_prefixed_and_sub() {
+        let dat = [0x03, b'a', b'b', b'c', 0x00, 0x02, b'x', b'y', 0x01, 0x02];
+        let mut c = ByteCursor::new(&dat);
+        let s = c.read_prefixed_bytes().unwrap();
+        assert_eq!(s, b"abc");
+        assert_eq!(s.as_ptr(), dat[1..].as_ptr());
+        assert_eq!(c.read_prefixed_bytes_with(2, Endian::Big), Ok(&b"xy"[..]));
+        let mut peek = c;
+        assert_eq!(peek.read_prefixed_bytes_with(3, Endian::Big),
+            Err(CursorError::UnsupportedWidth { offset: 8, width: 3 }));
+        let wide = [2, 0, 0, 0, 0, 0, 0, 0, b'p', b'q'];
+        assert_eq!(ByteCursor::new(&wide).read_prefixed_bytes_with(8, Endian::Little), Ok(&b"pq"[..]));
+
+        let mut sub = c.sub_cursor(2).unwrap();
+        assert_eq!(sub.read_u8(), Ok(1));
+        assert_eq!(sub.read_u16(Endian::Little),
+            Err(CursorError::UnexpectedEof { offset: 9, needed: 2, available: 1 }));
+        assert_eq!(c.sub_cursor(1).unwrap_err(),
+            CursorError::UnexpectedEof { offset: 10, needed: 1, available: 0 });
+    }
+}
@@ -0,0 +1,57 @@
+use std::cell::{Cell, Ref, RefCell, RefMut};
+
+// 世代カウンタ付きの共有バッファ。
+// 可変借用するたびに世代が進むので、作成時の世代を覚えておけば
+// 「自分が作られた後にバッファが書き換えられたか」を判定できる。
+// 中身のRefCellは外に出さないので、世代を進めずに書き換えることはできない。
+#[derive(Debug, Default)]
+pub struct GenBuffer<T> {
+    dat: RefCell<Vec<T>>,
+    generation: Cell<u64>,
+}
+
+impl<T> GenBuffer<T> {
+    pub fn new(dat: Vec<T>) -> Self {
+        GenBuffer {
+            dat: RefCell::new(dat),
+            generation: Cell::new(0),
+        }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.get()
+    }
+
+    pub fn borrow(&self) -> Ref<'_, Vec<T>> {
+        self.dat.borrow()
+    }
+
+    // 書き換えるかどうかに関わらず、可変借用した時点で世代を進める
+    pub fn borrow_mut(&self) -> RefMut<'_, Vec<T>> {
+        let r = self.dat.borrow_mut();
+        self.generation.set(self.generation.get() + 1);
+        r
+    }
+
+    pub fn into_inner(self) -> Vec<T> {
+        self.dat.into_inner()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::gen_buf::*;
+
+    #[test]
+    // 目的：可変借用のたびに世代が進むかを確認する
+    fn test_gen_buffer_generation() {
+        let buf = GenBuffer::new(vec![1, 2, 3]);
+        assert_eq!(buf.generation(), 0);
+        assert_eq!(buf.borrow().len(), 3);
+        assert_eq!(buf.generation(), 0);
+        buf.borrow_mut().push(4);
+        buf.borrow_mut().push(5);
+        assert_eq!(buf.generation(), 2);
+        assert_eq!(buf.into_inner(), vec![1, 2, 3, 4, 5]);
+    }
+}
@@ -1,6 +1,12 @@
 use std::{
-    rc::Rc, cell::RefCell, str::FromStr,
+    rc::Rc, cell::Ref, str::FromStr, ops::RangeInclusive,
 };
+use range_spec::{RangeSpecError, SliceError};
+use gen_buf::GenBuffer;
+use cursor::{ByteCursor, Endian};
+use cow_slice::CowSliceArray;
+use person::{Person, format::NameStyle, fuzzy::PersonMatcher, reading::{NameReading, by_reading}, directory::PersonDirectory};
+use name::kana::Romaji;
 use with_locals::with;
 
 #[derive(Debug)]
@@ -31,29 +37,85 @@ fn main_1() {
     println!("Hello, world! {:?}", p);
 }
 
+pub mod range_spec;
+pub mod cursor;
+pub mod cow_slice;
+pub mod str_slice;
+
 #[derive(Debug)]
-struct SliceHolder<'a> {
-    s: Option<&'a [u8]>,
+struct SliceHolder<'a, T = u8> {
+    s: Option<&'a [T]>,
+    // 要求された範囲と、切り出せなかった場合はその理由
+    range: RangeInclusive<usize>,
+    err: Option<SliceError>,
+    // 共有バッファ (GenBuffer) に対するviewの場合、作成時のバッファの世代。それ以外は0
+    generation: u64,
+}
+
+impl<'a, T> SliceHolder<'a, T> {
+    fn resolve(data: &'a [T], range: RangeInclusive<usize>) -> SliceHolder<'a, T> {
+        match range_spec::resolve(data, &range) {
+            Ok(s) => SliceHolder { s: Some(s), range, err: None, generation: 0 },
+            Err(e) => SliceHolder { s: None, range, err: Some(e), generation: 0 },
+        }
+    }
+
+    // sliceを持たず、範囲と世代だけを持つview。読む時にバッファから切り出す
+    fn detached(range: RangeInclusive<usize>, generation: u64) -> SliceHolder<'a, T> {
+        SliceHolder { s: None, range, err: None, generation }
+    }
+
+    fn print_s(&self)
+        where T: std::fmt::Debug
+    {
+        match &self.err {
+            Some(e) => println!("my data ({:?}): None ({})", self.range, e),
+            None => println!("my data ({:?}): {:?}", self.range, self.s),
+        }
+    }
 }
 
-impl<'a> SliceHolder<'a> {
-    fn print_s(&self) {
-        println!("my data: {:?}", self.s);
+impl<'a> SliceHolder<'a, u8> {
+    // 切り出せていればカーソルを返す。エラーのオフセットは元のデータ基準
+    fn cursor(&self) -> Option<ByteCursor<'a>> {
+        self.s.map(|s| ByteCursor::with_base(s, *self.range.start()))
     }
 }
 
 #[derive(Debug)]
 struct SliceArray<'a> {
+    dat: &'a [u8],
     ss: Vec<SliceHolder<'a>>,
 }
 
 impl<'a> SliceArray<'a> {
+    const DEFAULT_SPEC: &'static str = "..=3,2..=3,9..=13";
+
     fn new(data: &'a [u8]) -> SliceArray<'a> {
-        let mut sa = SliceArray { ss: vec![] };
-        sa.ss.push(SliceHolder { s: data.get(..=3) });
-        sa.ss.push(SliceHolder { s: data.get(2..=3) });
-        sa.ss.push(SliceHolder { s: data.get(9..=13) });
-        sa
+        SliceArray::from_spec(data, SliceArray::DEFAULT_SPEC).unwrap()
+    }
+
+    fn from_spec(data: &'a [u8], spec: &str) -> Result<SliceArray<'a>, RangeSpecError> {
+        Ok(SliceArray::with_ranges(data, range_spec::parse_range_spec(spec)?))
+    }
+
+    fn with_ranges(data: &'a [u8], ranges: Vec<RangeInclusive<usize>>) -> SliceArray<'a> {
+        SliceArray {
+            dat: data,
+            ss: ranges.into_iter().map(|r| SliceHolder::resolve(data, r)).collect(),
+        }
+    }
+
+    // 書き込み可能なviewにする。切り出せなかった範囲は除く (diagnosticsで分かる)
+    fn to_cow(&self) -> CowSliceArray<'a, u8> {
+        let ranges = self.ss.iter().filter(|v| v.err.is_none()).map(|v| v.range.clone()).collect();
+        // 切り出せた範囲だけなので失敗しない
+        CowSliceArray::new(self.dat, ranges).unwrap()
+    }
+
+    // 切り出せなかった範囲の添字と理由
+    fn diagnostics(&self) -> impl Iterator<Item = (usize, &SliceError)> {
+        self.ss.iter().enumerate().filter_map(|(i, v)| v.err.as_ref().map(|e| (i, e)))
     }
 }
 
@@ -64,148 +126,216 @@ fn main_2() {
     // vとsaの生存期間が一致するのでOKOK。
     // 本音としては、SliceArrayにデータをすべて押し込みたい
     println!("slice array => {:?}", sa);
+    sa.diagnostics().for_each(|(i, e)| println!("slice array[{}] => {}", i, e));
+    if let Some(mut c) = sa.ss[1].cursor() {
+        println!("slice array[1] as u16(BE) => {:?}", c.read_u16(Endian::Big));
+        println!("slice array[1] as u16(BE) => {:?}", c.read_u16(Endian::Big));
+    }
     sa.ss.into_iter().for_each(|v| v.print_s());
+
+    let sa = SliceArray::from_spec(v.as_slice(), "0..=1,6..9").unwrap();
+    let mut ca = sa.to_cow();
+    if let Some(s) = ca.get_mut(1) {
+        s[0] = 70;
+    }
+    println!("slice array(cow) => {:?}, materialized => {:?}", ca.get(1), ca.materialize());
+
+    // 文字列の場合は文字の途中で切れる範囲をはじく
+    let ssa = str_slice::StrSliceArray::from_spec("林竜太", "0..=2,2..=3,3..=8", str_slice::Boundary::Char).unwrap();
+    println!("str slice array => {:?}", ssa);
+
+    match SliceArray::from_spec(v.as_slice(), "1..4,8,x..=2") {
+        Ok(sa) => println!("slice array(spec) => {:?}", sa),
+        Err(e) => println!("slice array(spec) => {}", e),
+    }
 }
 
 
-#[derive(Debug)]
-struct SliceSelfArray<'a> {
-    // Boxにする必要は必ずしもない？→
-    dat: Box<Vec<u8>>,
-    ss: Vec<SliceHolder<'a>>,
+// 自前のバッファと範囲だけを持ち、SliceHolderは必要な時にバッファから作る。
+// 以前はunsafeでdatへの参照を持たせていたが、それだとderive(Clone)したものが元のバッファを指してしまう。
+// viewがポインタを持たなければ、moveしてもcloneしても常に自分のバッファを指す。
+#[derive(Debug, Clone, PartialEq)]
+struct SliceSelfArray<T> {
+    // Boxにする必要は必ずしもない？→ Vecの中身は元々ヒープにあるので、Boxは要らなかった
+    dat: Vec<T>,
+    ranges: Vec<RangeInclusive<usize>>,
 }
 
-impl<'a> SliceSelfArray<'a> {
-    fn new() -> Self {
-        let mut sa = SliceSelfArray {
-            dat: Box::new(vec![1, 2, 3, 4, 5, 6, 7, 8, 9]),
-            ss: vec![]
-        };
-        // 自己メンバー参照するケースはunsafeが必要となる？？
-        // 参考：https://medium.com/@reduls/refers-other-field-in-the-same-struct-in-rust-777bb2075b8c
-        // unsafeでポインタの参照をとることによって、ライフタイムを新たに設定することをコンパイラ(Borrow checker??)に伝えている
-        let data: &'a [u8] = unsafe { &*(sa.dat.as_slice() as *const _) };
+impl<T> SliceSelfArray<T> {
+    // 自己メンバー参照するケースはunsafeが必要となる？？
+    // 参考：https://medium.com/@reduls/refers-other-field-in-the-same-struct-in-rust-777bb2075b8c
+    // unsafeでポインタの参照をとることによって、ライフタイムを新たに設定することをコンパイラ(Borrow checker??)に伝えている
+    // →参照を持たずに範囲だけを持てば、unsafeは要らない
+    fn new(dat: Vec<T>, ranges: Vec<RangeInclusive<usize>>) -> Self {
+        SliceSelfArray { dat, ranges }
+    }
+
+    fn from_spec(dat: Vec<T>, spec: &str) -> Result<Self, RangeSpecError> {
+        Ok(SliceSelfArray::new(dat, range_spec::parse_range_spec(spec)?))
+    }
 
-        // 関数化してもNG
-        //let data: &'a [u8] = sa._self_data();
-        sa.ss.push(SliceHolder { s: data.get(..=3) });
-        sa.ss.push(SliceHolder { s: data.get(2..=3) });
-        sa.ss.push(SliceHolder { s: data.get(9..=13) });
-        sa
+    fn get(&self, idx: usize) -> Option<SliceHolder<'_, T>> {
+        let range = self.ranges.get(idx)?.clone();
+        Some(SliceHolder::resolve(self.self_data(), range))
     }
 
-    fn _self_data(&self) -> &[u8] {
-        unsafe { &*(self.dat.as_slice() as *const _) }
+    fn ss(&self) -> Vec<SliceHolder<'_, T>> {
+        self.ranges.iter().map(|r| SliceHolder::resolve(self.self_data(), r.clone())).collect()
+    }
+
+    // 関数化してもNG (構造体の'aを返そうとしていたため)
+    // →戻り値を&selfの寿命に縛れば、普通の関数でよい
+    fn self_data(&self) -> &[T] {
+        self.dat.as_slice()
+    }
+
+    fn into_inner(self) -> Vec<T> {
+        self.dat
     }
 }
 
 fn main_3() {
-    let ssa = SliceSelfArray::new();
+    let ssa = SliceSelfArray::from_spec(vec![1u8, 2, 3, 4, 5, 6, 7, 8, 9], SliceArray::DEFAULT_SPEC).unwrap();
     println!("slice self array => {:?}", ssa);
-    ssa.ss.into_iter().for_each(|v| v.print_s());
+    ssa.ss().into_iter().for_each(|v| v.print_s());
+
+    // cloneしたものは自分のバッファを指すので、元を捨てても大丈夫
+    let ssa2 = ssa.clone();
+    let dat = ssa.into_inner();
+    println!("slice self array(clone) => {:?}, {:?}", ssa2.get(1), dat);
 }
 
-type RcRefCell<T> = std::rc::Rc<std::cell::RefCell<T>>;
 type RcCell<T> = std::rc::Rc<std::cell::Cell<T>>;
 
+pub mod gen_buf;
+
+// viewはsliceそのものではなく、範囲と作成時のバッファの世代だけを持つSliceHolder (detached)。
+// アクセスのたびに世代を確認してから範囲を解決するので、書き換え後のバッファを読むことはない。
 #[derive(Debug)]
-struct SliceRcArray<'a> {
-    dat: RcRefCell<Vec<u8>>,
-    ss: Vec<SliceHolder<'a>>,
+struct SliceRcArray {
+    dat: Rc<GenBuffer<u8>>,
+    ss: Vec<SliceHolder<'static>>,
 }
 
-impl<'a> SliceRcArray<'a> {
-    fn new() -> SliceRcArray<'a> {
+impl SliceRcArray {
+    fn new() -> SliceRcArray {
         let mut sra = SliceRcArray {
-            dat: Rc::new(RefCell::new(vec![1, 2, 3, 4, 5, 6, 7, 8, 9])),
+            dat: Rc::new(GenBuffer::new(vec![1, 2, 3, 4, 5, 6, 7, 8, 9])),
             ss: vec![],
         };
-        // Rc<RefCell>で保持しても、結局はunsafeが必要になるぽい
-        let data: &'a [u8] = unsafe { &*(sra.dat.borrow().as_slice() as *const _) };
-        sra.ss.push(SliceHolder { s: data.get(..=3) });
+        // Rc<RefCell>で保持してunsafeでsliceを持つと、書き換え後に解放済みのメモリを読んでしまう。
+        // →範囲と世代だけを持っておいて、読む時に借用する
+        sra.push_view(0..=3);
         sra
     }
+
+    fn push_view(&mut self, range: RangeInclusive<usize>) {
+        let generation = self.dat.generation();
+        self.ss.push(SliceHolder::detached(range, generation));
+    }
+
+    // バッファが作成時から書き換えられていればSliceError::Stale
+    fn get(&self, idx: usize) -> Option<Result<Ref<'_, [u8]>, SliceError>> {
+        let h = self.ss.get(idx)?;
+        let current = self.dat.generation();
+        if h.generation != current {
+            return Some(Err(SliceError::Stale { created: h.generation, current }));
+        }
+        let dat = self.dat.borrow();
+        if let Err(e) = range_spec::resolve(&dat, &h.range) {
+            return Some(Err(e));
+        }
+        Some(Ok(Ref::map(dat, |v| &v[h.range.clone()])))
+    }
+
+    // 全viewを現在の世代で取り直す (範囲はそのまま)
+    fn refresh(&mut self) {
+        let generation = self.dat.generation();
+        self.ss.iter_mut().for_each(|v| v.generation = generation);
+    }
 }
 
 fn main_4() {
     let mut sra = SliceRcArray::new();
     println!("1: slice rc array => {:?}", sra);
+    println!("1: view => {:?}", sra.get(0));
 
     // 参照元を変更しちゃうと、SliceHolderが無効になっちゃう
-    sra.dat.as_ref().borrow_mut().push(10);
-    sra.dat.as_ref().borrow_mut().push(12);
+    // →世代が進むので、古いviewはエラーになる
+    sra.dat.borrow_mut().push(10);
+    sra.dat.borrow_mut().push(12);
     println!("2: slice rc array => {:?}", sra);
+    println!("2: view => {:?}", sra.get(0));
+
+    sra.refresh();
+    println!("3: view => {:?}", sra.get(0));
 }
 
 // 参考ページ: https://arunanshub.hashnode.dev/self-referential-structs-in-rust
 // 自己参照構造体 (親と子で互いに参照を持ち合う的な話)
 
-#[derive(Debug)]
-struct Person<'me> {
-    full_name: String,
-    name: &'me str,
-    surname: &'me str,
-}
+pub mod name;
+pub mod person;
 
-impl<'me> Person<'me> {
-    fn new(full_name: String) -> Option<Self> {
-        if full_name.split(" ").count() != 2 {
-            None
-        } else {
-            Some(Self {
-                full_name,
-                name: "".into(),
-                surname: "".into(),
-            })
-        }
+#[with]
+fn main_5() {
+    let mut p = Person::new("Ryuta Hayashi".into()).unwrap();
+    println!("p => {:?}", p);
+    // 以前はinit()でppのライフタイム = pのライフタイムとなり、full_nameを書き換えられなかった。
+    // →full_nameは非公開になり、set_full_nameで書き換えるとname/surnameも導き直される
+    p.set_full_name("Haruka Hayashi".into()).unwrap();
+    println!("p => {:?}", p);
+    // 解釈できない名前は失敗し、元の値はそのまま
+    if let Err(e) = p.set_full_name("Haruka".into()) {
+        println!("p => {} / {:?}", e, p);
     }
 
-    fn new_and_init(full_name: String) -> Option<Self> {
-        let p = Person::new(full_name);
-        if p.is_none() {
-            None
-        } else {
-            let p = p.unwrap();
-            let p = p.init3();
-            //let p: &'me Person = unsafe { &*(p.init() as *const _) };
-            Some(p)
-        }
+    let mut p2 = Person::new("Aiko Hayashi".into()).unwrap();
+    // 以前はここでnameとsurnameが無効化されていた
+    p2.set_full_name("Fumika Hayashi".into()).unwrap();
+    println!("p2 => {:?}", p2);
+    for style in [NameStyle::Western, NameStyle::SurnameComma, NameStyle::Initials, NameStyle::Official, NameStyle::San] {
+        println!("p2({:?}) => {}", style, p2.format_style(style));
     }
 
-    fn init3(mut self) -> Person<'me> {
-        let ref mut words = self.full_name.split(" ");
-        // unsafeであれば、
-        self.name = unsafe { &*(words.next().unwrap() as *const _) };
-        self.surname = unsafe { &*(words.next().unwrap() as *const _) };
-        self
+    // 姓が先の名前や空白のない名前
+    for full_name in ["Hayashi, Ryuta", "林　竜太", "林竜太", "Ludwig van Beethoven"] {
+        match Person::new(full_name.into()) {
+            Some(p) => println!("p3 => {:?}", p.parts()),
+            None => println!("p3 => {} is not a name", full_name),
+        }
     }
 
-    // newと初期化を別々に呼び出す必要があるが、full_nameを書き換えるとボローチェッカーに怒られるようになるのでありっちゃーありか！？
-    //  自己参照のシグニチャーを移動させる。
-    fn init(self: &'me mut Person<'me>) -> &Self {
-        let ref mut words = self.full_name.split(" ");
-        self.name = words.next().unwrap();
-        self.surname = words.next().unwrap();
-        self
+    // 取り込んだ名簿の重複らしきものをまとめる
+    let people: Vec<Person> = ["Ryuta Hayashi", "Aiko Sato", "RYŪTA HAYASHI", "Hayasi, Ryuuta", "Aiko Satou"].iter()
+        .filter_map(|v| Person::new(v.to_string()))
+        .collect();
+    for c in PersonMatcher::default().clusters(&people) {
+        let names: Vec<&str> = c.members.iter().map(|i| people[*i].full_name()).collect();
+        println!("p4 => {:?} ({:.2})", names, c.score);
     }
-}
 
-#[with]
-fn main_5() {
-    let mut p = Person::new("Ryuta Hayashi".into()).unwrap();
-    {
-        let pp = p.init();
-        println!("p => {:?}", pp);
+    // 読みの五十音順に並べる。漢字の名前は読みを付けておく
+    let mut people = vec![
+        Person::new("Aiko Sato".into()).unwrap(),
+        Person::new("林 竜太".into()).unwrap().with_reading(NameReading::new("はやし", "りゅうた").unwrap()),
+        Person::new("イトウ ケイ".into()).unwrap(),
+    ];
+    people.sort_by(by_reading);
+    for p in &people {
+        println!("p5 => {} {:?}", p.full_name(), p.reading().map(|r| r.romaji(Romaji::Hepburn)));
     }
-    // 以下のコードはコンパイルできない。
-    // →init()でppのライフタイム = pのライフタイムとなって、ppがスコープを抜けて無効となっているため
-    //p.full_name = "Haruka Hayashi".into();
-
-    let mut p2 = (Person::new_and_init("Aiko Hayashi".into())).unwrap();
-    // 以下で、nameとsurnameは無効化される。
-    p2.full_name = "Fumika Hayashi".into();
 
-    println!("p2 => {:?}", p2);
+    // 索引のキーはfull_nameからの借用
+    let mut dir = PersonDirectory::new();
+    for p in people {
+        dir.insert(p);
+    }
+    let id = dir.insert(Person::new("Haruka Hayashi".into()).unwrap());
+    dir.update(id, |p| p.set_full_name("Haruka Sato".into())).unwrap().unwrap();
+    for (id, p) in dir.by_surname("Sato") {
+        println!("p6 => {:?} {}", id, p.full_name());
+    }
 }
 
 pub mod sub1;
@@ -258,61 +388,49 @@ fn main_6() {
 // ---------------------------------------------------------------------------------
 // 仮想的なPDFのデータ構造
 
-#[derive(Debug)]
-struct XRefTableEntry<'doc> {
-    entry_type: i32,
-    entry_data: &'doc [u8],
-}
+pub mod pdf;
 
-impl<'doc> XRefTableEntry<'doc> {
-    fn new(entry_type: i32, data: &'doc [u8]) -> Self {
-        XRefTableEntry { entry_type: 0, entry_data: data }
+fn main_7() {
+    let dat = b"%PDF-1.4\n1 0 obj\n<< /Type /Catalog >>\nendobj\n\
+        xref\n0 2\n0000000000 65535 f\r\n0000000009 00000 n\r\n\
+        trailer\n<< /Size 2 /Root 1 0 R >>\nstartxref\n45\n%%EOF\n";
+    let d = match pdf::Doc::new(dat) {
+        Ok(d) => d,
+        Err(e) => {
+            println!("main_7: {}", e);
+            return;
+        }
+    };
+    for e in d.table().unwrap().entries() {
+        println!("main_7: {} {} {:?}", e.obj_num, e.gen, e.kind);
+    }
+    if let Some(t) = d.trailer() {
+        println!("main_7: size={:?} root={:?}", t.size(), t.root());
+    }
+    match d.catalog() {
+        Ok(v) => println!("main_7: catalog => {:?}", v),
+        Err(e) => println!("main_7: {}", e),
     }
-}
-
-#[derive(Debug)]
-struct XRefTable<'doc> {
-    doc: &'doc Doc<'doc>,
-    table_data: Vec<XRefTableEntry<'doc>>
-}
 
-impl <'doc> XRefTable<'doc> {
-    fn new(doc: &'doc Doc) -> Self {
-        let mut xtbl = XRefTable {
-            doc,
-            table_data: Vec::new(),
-        };
-        xtbl.table_data.push(XRefTableEntry::new(1, doc.dat));
-        xtbl
+    // startxrefが壊れたものは、本体を走査して作り直す
+    let broken = String::from_utf8_lossy(dat).replace("startxref\n45", "startxref\n4500");
+    match pdf::Doc::repair(broken.as_bytes()) {
+        Ok(d) => println!("main_7: repaired {:?} => root={:?}", d.repair_report(), d.trailer().and_then(|t| t.root())),
+        Err(e) => println!("main_7: {}", e),
     }
-}
 
-#[derive(Debug)]
-struct Doc<'a> {
-    dat: &'a [u8],
-    table: Option<XRefTable<'a>>,
-}
+    // DocのDebugは元のバイト列を長さだけにするので、そのままプリントできる。
+    println!("main_7: {:?}", d);
+    print!("{}", d.dump());
 
-impl <'a> Doc<'a> {
-    fn new(dat: &'a [u8]) -> Self {
-        let mut d = Doc {
-            dat,
-            table: None
-        };
-        let tbl = Some(XRefTable::new(unsafe {
-            &*(&d as *const _)
-        }));
-        d.table = tbl;
-        d
-    }
+    // バイト列を自分で持つOwnedDocは、関数から返したり他のスレッドに渡したりできる
+    let owned = load_owned(dat);
+    let h = std::thread::spawn(move || owned.map(|d| format!("{:?}", d.catalog())));
+    println!("main_7: owned => {:?}", h.join().unwrap());
 }
 
-fn main_7() {
-    let v = vec![1, 2, 3, 4, 5, 6, 7, 8];
-    let d = Doc::new(v.as_slice());
-
-    // docが再帰的にプリントされる事により以下は落ちる。
-    //println!("main_7: {:?}", d);
+fn load_owned(dat: &[u8]) -> Result<pdf::OwnedDoc, pdf::PdfError> {
+    pdf::OwnedDoc::from_vec(dat.to_vec())
 }
 
 // -------------------------------------------------------------------------------------------
@@ -509,4 +627,65 @@ fn main() {
     main_6();
     main_7();
     main_8();
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[test]
+    // 目的：既定の範囲で作ったSliceArrayも、切り出せた範囲だけで書き込み可能なviewにできるかを確認する
+    fn test_slice_array_to_cow() {
+        let v = vec![1u8, 2, 3, 4, 5, 6, 7, 8, 9];
+        let sa = SliceArray::new(&v);
+        assert_eq!(sa.diagnostics().count(), 1);
+        let mut ca = sa.to_cow();
+        assert_eq!(ca.len(), 2);
+        assert_eq!(ca.get(1), Some(&[3, 4][..]));
+        ca.get_mut(1).unwrap()[0] = 30;
+        assert_eq!(ca.materialize(), vec![1, 2, 30, 4, 5, 6, 7, 8, 9]);
+        assert_eq!(v[2], 3);
+    }
+
+    #[test]
+    // 目的：cloneしたSliceSelfArrayが元と等しく、元を捨てても自分のバッファを指すかを確認する
+    fn test_slice_self_array_clone() {
+        let ssa = SliceSelfArray::from_spec(vec![1u8, 2, 3, 4, 5, 6, 7, 8, 9], SliceArray::DEFAULT_SPEC).unwrap();
+        let ssa2 = ssa.clone();
+        assert_eq!(ssa, ssa2);
+        let dat = ssa.into_inner();
+        assert_eq!(dat, vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
+        let s = ssa2.get(1).unwrap().s.unwrap();
+        assert_eq!(s, &[3, 4]);
+        assert_eq!(s.as_ptr(), ssa2.self_data()[2..].as_ptr());
+        assert_ne!(s.as_ptr(), dat[2..].as_ptr());
+    }
+
+    #[test]
+    // 目的：範囲外の範囲はエラーとして残り、中身が違えば等しくないかを確認する
+    fn test_slice_self_array_get() {
+        let ssa = SliceSelfArray::from_spec(vec!['a', 'b', 'c'], "0..=1,2..=5").unwrap();
+        assert_eq!(ssa.get(0).unwrap().s, Some(&['a', 'b'][..]));
+        assert_eq!(ssa.ss()[1].err, Some(SliceError::PastEnd { range: 2..=5, len: 3 }));
+        assert!(ssa.get(2).is_none());
+        assert_ne!(ssa, SliceSelfArray::from_spec(vec!['a', 'b', 'x'], "0..=1,2..=5").unwrap());
+        assert_eq!(ssa.into_inner(), vec!['a', 'b', 'c']);
+    }
+
+    #[test]
+    // 目的：書き換えの前に作ったviewが書き換え後はStaleになり、refreshで読めるようになるかを確認する
+    fn test_slice_rc_array_stale() {
+        let mut sra = SliceRcArray::new();
+        assert_eq!(sra.get(0).unwrap().as_deref(), Ok(&[1, 2, 3, 4][..]));
+        sra.dat.borrow_mut()[0] = 10;
+        sra.push_view(7..=8);
+        assert_eq!(sra.get(0).unwrap().err(), Some(SliceError::Stale { created: 0, current: 1 }));
+        assert_eq!(sra.get(1).unwrap().as_deref(), Ok(&[8, 9][..]));
+        assert!(sra.get(2).is_none());
+
+        sra.dat.borrow_mut().truncate(8);
+        sra.refresh();
+        assert_eq!(sra.get(0).unwrap().as_deref(), Ok(&[10, 2, 3, 4][..]));
+        assert_eq!(sra.get(1).unwrap().err(), Some(SliceError::PastEnd { range: 7..=8, len: 8 }));
+    }
+}
@@ -0,0 +1,326 @@
+use std::ops::Range;
+use thiserror::Error;
+
+pub mod kana;
+
+// 人名をgiven(名)/middle/family(姓)/suffixに分解する。
+// 結果はfull_name中のバイト範囲 (NameSpans) で返すので、元の文字列からそのまま借用したviewが作れる。
+//
+//   - 空白は半角・全角(U+3000)どちらも区切りとして扱う
+//   - "van", "de" などの前置詞は後ろの姓にくっつける ("Ludwig van Beethoven" → 姓 "van Beethoven")
+//   - 末尾の "Jr." "III" などはsuffixとする
+//   - "Hayashi, Ryuta" のようにカンマがあれば、カンマの前を姓とする
+//   - 漢字・かなを含む名前はAutoの場合に姓が先とみなす
+//   - "林竜太" のように空白のない名前は、よくある姓の一覧か family_len の指定で切り分ける
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NameOrder {
+    // 漢字・かな・ハングルを含めば姓が先、それ以外は名が先
+    #[default]
+    Auto,
+    GivenFirst,
+    FamilyFirst,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NameOptions {
+    pub order: NameOrder,
+    // 空白のない名前の姓の文字数。Noneなら推測する
+    pub family_len: Option<usize>,
+}
+
+impl NameOptions {
+    pub fn family_first() -> Self {
+        NameOptions { order: NameOrder::FamilyFirst, family_len: None }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum NameError {
+    #[error("name is empty")]
+    Empty,
+    #[error("name {0:?} has only one part")]
+    SinglePart(String),
+    #[error("family name length {len} does not fit in name {name:?}")]
+    BadFamilyLen { name: String, len: usize },
+}
+
+// full_name中のバイト範囲
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameSpans {
+    pub given: Range<usize>,
+    pub middle: Option<Range<usize>>,
+    pub family: Range<usize>,
+    pub suffix: Option<Range<usize>>,
+    // 実際に解釈した並び (GivenFirst/FamilyFirstのどちらか)
+    pub order: NameOrder,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NameParts<'a> {
+    pub given: &'a str,
+    pub middle: Option<&'a str>,
+    pub family: &'a str,
+    pub suffix: Option<&'a str>,
+}
+
+impl NameSpans {
+    // parse_nameに渡したのと同じ文字列を渡すこと
+    pub fn parts<'a>(&self, full_name: &'a str) -> NameParts<'a> {
+        NameParts {
+            given: &full_name[self.given.clone()],
+            middle: self.middle.clone().map(|r| &full_name[r]),
+            family: &full_name[self.family.clone()],
+            suffix: self.suffix.clone().map(|r| &full_name[r]),
+        }
+    }
+}
+
+const PARTICLES: &[&str] = &[
+    "van", "von", "der", "den", "de", "del", "della", "di", "da", "dos", "das", "du",
+    "la", "le", "ter", "ten", "bin", "ibn", "al", "st.",
+];
+
+const SUFFIXES: &[&str] = &["jr", "sr", "ii", "iii", "iv", "phd", "md", "esq"];
+
+// 空白なしの名前を切り分ける時に使う、よくある日本の姓
+const COMMON_FAMILY_NAMES: &[&str] = &[
+    "佐々木", "長谷川", "佐藤", "鈴木", "高橋", "田中", "伊藤", "渡辺", "山本", "中村", "小林",
+    "加藤", "吉田", "山田", "山口", "松本", "井上", "木村", "清水", "斎藤", "斉藤", "池田",
+    "橋本", "阿部", "石川", "山下", "中島", "石井", "小川", "前田", "岡田", "藤田", "後藤",
+    "近藤", "村上", "遠藤", "青木", "坂本", "福田", "太田", "西村", "藤井", "金子", "岡本",
+    "藤原", "中野", "三浦", "原田", "中川", "松田", "竹内", "小野", "田村", "中山", "和田",
+    "石田", "森田", "上田", "柴田", "酒井", "工藤", "横山", "宮崎", "宮本", "内田", "高木",
+    "林", "森", "原", "関", "辻", "堀", "東", "西", "南", "北",
+];
+
+#[derive(Debug, Clone)]
+struct Token {
+    range: Range<usize>,
+    // 直後にカンマがあった
+    comma: bool,
+}
+
+pub fn parse_name(full_name: &str, opts: &NameOptions) -> Result<NameSpans, NameError> {
+    let mut tokens = tokenize(full_name);
+    if tokens.is_empty() {
+        return Err(NameError::Empty);
+    }
+    let word = |t: &Token| &full_name[t.range.clone()];
+
+    // 末尾のsuffix。カンマ付きの "Hayashi, Ryuta, Jr." の "Jr." もここで外れる。
+    // "Naosuke Ii" (井伊) のように姓がsuffixと同じ綴りの場合があるので、
+    // カンマの後か、外しても2語以上残る場合だけsuffixとみなす
+    let mut suffix: Option<Range<usize>> = None;
+    while tokens.len() > 1
+        && (tokens.len() > 2 || tokens[tokens.len() - 2].comma)
+        && is_suffix(word(tokens.last().unwrap()))
+    {
+        let t = tokens.pop().unwrap();
+        suffix = Some(t.range.start..suffix.map_or(t.range.end, |v| v.end));
+    }
+    if let Some(t) = tokens.last_mut() {
+        t.comma = false;
+    }
+
+    let cjk = full_name.chars().any(is_cjk);
+    let order = match opts.order {
+        NameOrder::Auto if cjk => NameOrder::FamilyFirst,
+        NameOrder::Auto => NameOrder::GivenFirst,
+        v => v,
+    };
+
+    if tokens.len() == 1 {
+        let t = &tokens[0];
+        if !cjk {
+            return Err(NameError::SinglePart(full_name.to_owned()));
+        }
+        return split_unspaced(full_name, t.range.clone(), order, opts.family_len)
+            .map(|(given, family)| NameSpans { given, middle: None, family, suffix, order });
+    }
+
+    let span = |v: &[Token]| -> Option<Range<usize>> {
+        Some(v.first()?.range.start..v.last()?.range.end)
+    };
+
+    // カンマがあれば "姓, 名 ミドル"
+    if let Some(i) = tokens.iter().position(|t| t.comma) {
+        // 末尾のカンマは外してあるので、カンマの後ろには必ず1語以上ある
+        let (family, rest) = tokens.split_at(i + 1);
+        return Ok(NameSpans {
+            given: rest[0].range.clone(),
+            middle: span(&rest[1..]),
+            family: span(family).unwrap(),
+            suffix,
+            order: NameOrder::FamilyFirst,
+        });
+    }
+
+    let spans = match order {
+        NameOrder::FamilyFirst => NameSpans {
+            family: tokens[0].range.clone(),
+            given: tokens[1].range.clone(),
+            middle: span(&tokens[2..]),
+            suffix,
+            order,
+        },
+        _ => {
+            // 姓の前の前置詞を姓に含める (先頭は名なので含めない)
+            let mut family_start = tokens.len() - 1;
+            while family_start > 1 && is_particle(word(&tokens[family_start - 1])) {
+                family_start -= 1;
+            }
+            NameSpans {
+                given: tokens[0].range.clone(),
+                middle: span(&tokens[1..family_start]),
+                family: span(&tokens[family_start..]).unwrap(),
+                suffix,
+                order,
+            }
+        }
+    };
+    Ok(spans)
+}
+
+fn tokenize(s: &str) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut start = None;
+    for (i, c) in s.char_indices().chain(std::iter::once((s.len(), ' '))) {
+        if c.is_whitespace() || c == ',' || c == '，' || c == '、' {
+            if let Some(st) = start.take() {
+                tokens.push(Token { range: st..i, comma: false });
+            }
+            if c != ' ' && !c.is_whitespace() {
+                if let Some(t) = tokens.last_mut() {
+                    t.comma = true;
+                }
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    tokens
+}
+
+// 空白のない名前を (名, 姓) の範囲に分ける
+fn split_unspaced(
+    full_name: &str,
+    range: Range<usize>,
+    order: NameOrder,
+    family_len: Option<usize>,
+) -> Result<(Range<usize>, Range<usize>), NameError> {
+    let word = &full_name[range.clone()];
+    let n = word.chars().count();
+    if n < 2 {
+        return Err(NameError::SinglePart(full_name.to_owned()));
+    }
+    let k = match family_len {
+        Some(k) => k,
+        None if order == NameOrder::FamilyFirst => COMMON_FAMILY_NAMES.iter()
+            .filter(|v| word.starts_with(**v))
+            .map(|v| v.chars().count())
+            .filter(|k| *k < n)
+            .max()
+            .unwrap_or(if n <= 3 { 1 } else { 2 }),
+        None => if n <= 3 { 1 } else { 2 },
+    };
+    if k == 0 || k >= n {
+        return Err(NameError::BadFamilyLen { name: full_name.to_owned(), len: k });
+    }
+    let at = |chars: usize| range.start + word.char_indices().nth(chars).unwrap().0;
+    if order == NameOrder::GivenFirst {
+        let split = at(n - k);
+        Ok((range.start..split, split..range.end))
+    } else {
+        let split = at(k);
+        Ok((split..range.end, range.start..split))
+    }
+}
+
+fn is_particle(w: &str) -> bool {
+    PARTICLES.iter().any(|v| v.eq_ignore_ascii_case(w))
+}
+
+fn is_suffix(w: &str) -> bool {
+    let w = w.trim_end_matches('.').replace('.', "");
+    SUFFIXES.iter().any(|v| v.eq_ignore_ascii_case(&w))
+}
+
+// 漢字・かな・ハングル
+pub fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3005}' | '\u{3040}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}'
+        | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}'
+        | '\u{FF66}'..='\u{FF9F}' | '\u{AC00}'..='\u{D7AF}' | '\u{20000}'..='\u{2A6DF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::name::*;
+
+    fn parts<'a>(s: &'a str, opts: &NameOptions) -> NameParts<'a> {
+        parse_name(s, opts).unwrap().parts(s)
+    }
+
+    #[test]
+    // 目的：ミドルネーム・前置詞・suffixを含む名前が分解できるかを確認する
+    fn test_parse_name_western() {
+        let opts = NameOptions::default();
+        assert_eq!(parts("Ryuta Hayashi", &opts),
+            NameParts { given: "Ryuta", middle: None, family: "Hayashi", suffix: None });
+        assert_eq!(parts("John Ronald Reuel Tolkien", &opts),
+            NameParts { given: "John", middle: Some("Ronald Reuel"), family: "Tolkien", suffix: None });
+        assert_eq!(parts("Ludwig van  Beethoven", &opts),
+            NameParts { given: "Ludwig", middle: None, family: "van  Beethoven", suffix: None });
+        assert_eq!(parts("Martin Luther King Jr.", &opts),
+            NameParts { given: "Martin", middle: Some("Luther"), family: "King", suffix: Some("Jr.") });
+        assert_eq!(parts("Hayashi, Ryuta", &opts),
+            NameParts { given: "Ryuta", middle: None, family: "Hayashi", suffix: None });
+    }
+
+    #[test]
+    // 目的：suffixと同じ綴りの姓が、姓しか候補が無い場合は姓として残るかを確認する
+    fn test_parse_name_suffix_like_family() {
+        let opts = NameOptions::default();
+        assert_eq!(parts("Naosuke Ii", &opts),
+            NameParts { given: "Naosuke", middle: None, family: "Ii", suffix: None });
+        assert_eq!(parts("Marcus Md", &opts),
+            NameParts { given: "Marcus", middle: None, family: "Md", suffix: None });
+        assert_eq!(parts("Naosuke Ii III", &opts),
+            NameParts { given: "Naosuke", middle: None, family: "Ii", suffix: Some("III") });
+        assert_eq!(parts("Ii, Naosuke, II", &opts),
+            NameParts { given: "Naosuke", middle: None, family: "Ii", suffix: Some("II") });
+        assert_eq!(parts("John Smith Jr. PhD", &opts),
+            NameParts { given: "John", middle: None, family: "Smith", suffix: Some("Jr. PhD") });
+    }
+
+    #[test]
+    // 目的：姓が先の名前、全角空白、空白なしの名前が分解できるかを確認する
+    fn test_parse_name_family_first() {
+        assert_eq!(parts("Hayashi Ryuta", &NameOptions::family_first()),
+            NameParts { given: "Ryuta", middle: None, family: "Hayashi", suffix: None });
+        let opts = NameOptions::default();
+        assert_eq!(parts("林　竜太", &opts),
+            NameParts { given: "竜太", middle: None, family: "林", suffix: None });
+        assert_eq!(parts("林竜太", &opts),
+            NameParts { given: "竜太", middle: None, family: "林", suffix: None });
+        assert_eq!(parts("佐々木希", &opts),
+            NameParts { given: "希", middle: None, family: "佐々木", suffix: None });
+        let opts = NameOptions { family_len: Some(3), ..Default::default() };
+        assert_eq!(parts("小鳥遊ひな", &opts),
+            NameParts { given: "ひな", middle: None, family: "小鳥遊", suffix: None });
+    }
+
+    #[test]
+    // 目的：分解できない名前がエラーになるかを確認する
+    fn test_parse_name_error() {
+        let opts = NameOptions::default();
+        assert_eq!(parse_name("  ", &opts), Err(NameError::Empty));
+        assert_eq!(parse_name("Madonna", &opts), Err(NameError::SinglePart("Madonna".to_owned())));
+        assert_eq!(parse_name("Hayashi,", &opts), Err(NameError::SinglePart("Hayashi,".to_owned())));
+        assert_eq!(parse_name(", Ryuta Hayashi", &opts).unwrap().parts(", Ryuta Hayashi").family, "Hayashi");
+        let opts = NameOptions { family_len: Some(3), ..Default::default() };
+        assert_eq!(parse_name("林竜太", &opts),
+            Err(NameError::BadFamilyLen { name: "林竜太".to_owned(), len: 3 }));
+    }
+}
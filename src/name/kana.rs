@@ -0,0 +1,317 @@
+use std::cmp::Ordering;
+use thiserror::Error;
+
+// かな(ひらがな・カタカナ)とローマ字の相互変換、五十音順の比較。
+//
+//   - かな → ローマ字はヘボン式と訓令式を選べる (し → shi / si)
+//   - ローマ字 → かなはどちらの方式も受け付ける ("shi" も "si" も し)
+//   - 促音 (っ) は次の子音を重ね、撥音 (ん) は母音・yの前で "n'" とする
+//   - 長音符 (ー) は直前の母音を繰り返す
+// 変換は名前の読みを扱う程度の簡易なもの。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Romaji {
+    // し → shi、ち → chi、ん (b/m/pの前) → m
+    #[default]
+    Hepburn,
+    // し → si、ち → ti、ん → n
+    Kunrei,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum KanaError {
+    #[error("unexpected {found:?} at offset {offset}")]
+    Unexpected { offset: usize, found: char },
+    #[error("reading is empty")]
+    Empty,
+}
+
+// (ひらがな, ヘボン式, 訓令式)。ローマ字 → かなでは同じ綴りなら先にあるものを使う
+const TABLE: &[(&str, &str, &str)] = &[
+    ("きゃ", "kya", "kya"), ("きゅ", "kyu", "kyu"), ("きょ", "kyo", "kyo"),
+    ("しゃ", "sha", "sya"), ("しゅ", "shu", "syu"), ("しょ", "sho", "syo"), ("しぇ", "she", "sye"),
+    ("ちゃ", "cha", "tya"), ("ちゅ", "chu", "tyu"), ("ちょ", "cho", "tyo"), ("ちぇ", "che", "tye"),
+    ("にゃ", "nya", "nya"), ("にゅ", "nyu", "nyu"), ("にょ", "nyo", "nyo"),
+    ("ひゃ", "hya", "hya"), ("ひゅ", "hyu", "hyu"), ("ひょ", "hyo", "hyo"),
+    ("みゃ", "mya", "mya"), ("みゅ", "myu", "myu"), ("みょ", "myo", "myo"),
+    ("りゃ", "rya", "rya"), ("りゅ", "ryu", "ryu"), ("りょ", "ryo", "ryo"),
+    ("ぎゃ", "gya", "gya"), ("ぎゅ", "gyu", "gyu"), ("ぎょ", "gyo", "gyo"),
+    ("じゃ", "ja", "zya"), ("じゅ", "ju", "zyu"), ("じょ", "jo", "zyo"), ("じぇ", "je", "zye"),
+    ("びゃ", "bya", "bya"), ("びゅ", "byu", "byu"), ("びょ", "byo", "byo"),
+    ("ぴゃ", "pya", "pya"), ("ぴゅ", "pyu", "pyu"), ("ぴょ", "pyo", "pyo"),
+    ("ふぁ", "fa", "fa"), ("ふぃ", "fi", "fi"), ("ふぇ", "fe", "fe"), ("ふぉ", "fo", "fo"),
+    ("うぃ", "wi", "wi"), ("うぇ", "we", "we"),
+    ("ぢゃ", "ja", "zya"), ("ぢゅ", "ju", "zyu"), ("ぢょ", "jo", "zyo"),
+    ("あ", "a", "a"), ("い", "i", "i"), ("う", "u", "u"), ("え", "e", "e"), ("お", "o", "o"),
+    ("か", "ka", "ka"), ("き", "ki", "ki"), ("く", "ku", "ku"), ("け", "ke", "ke"), ("こ", "ko", "ko"),
+    ("さ", "sa", "sa"), ("し", "shi", "si"), ("す", "su", "su"), ("せ", "se", "se"), ("そ", "so", "so"),
+    ("た", "ta", "ta"), ("ち", "chi", "ti"), ("つ", "tsu", "tu"), ("て", "te", "te"), ("と", "to", "to"),
+    ("な", "na", "na"), ("に", "ni", "ni"), ("ぬ", "nu", "nu"), ("ね", "ne", "ne"), ("の", "no", "no"),
+    ("は", "ha", "ha"), ("ひ", "hi", "hi"), ("ふ", "fu", "hu"), ("へ", "he", "he"), ("ほ", "ho", "ho"),
+    ("ま", "ma", "ma"), ("み", "mi", "mi"), ("む", "mu", "mu"), ("め", "me", "me"), ("も", "mo", "mo"),
+    ("や", "ya", "ya"), ("ゆ", "yu", "yu"), ("よ", "yo", "yo"),
+    ("ら", "ra", "ra"), ("り", "ri", "ri"), ("る", "ru", "ru"), ("れ", "re", "re"), ("ろ", "ro", "ro"),
+    ("わ", "wa", "wa"), ("を", "o", "o"), ("ん", "n", "n"),
+    ("が", "ga", "ga"), ("ぎ", "gi", "gi"), ("ぐ", "gu", "gu"), ("げ", "ge", "ge"), ("ご", "go", "go"),
+    ("ざ", "za", "za"), ("じ", "ji", "zi"), ("ず", "zu", "zu"), ("ぜ", "ze", "ze"), ("ぞ", "zo", "zo"),
+    ("だ", "da", "da"), ("ぢ", "ji", "zi"), ("づ", "zu", "zu"), ("で", "de", "de"), ("ど", "do", "do"),
+    ("ば", "ba", "ba"), ("び", "bi", "bi"), ("ぶ", "bu", "bu"), ("べ", "be", "be"), ("ぼ", "bo", "bo"),
+    ("ぱ", "pa", "pa"), ("ぴ", "pi", "pi"), ("ぷ", "pu", "pu"), ("ぺ", "pe", "pe"), ("ぽ", "po", "po"),
+    ("ゔ", "vu", "vu"), ("ゐ", "i", "i"), ("ゑ", "e", "e"),
+    ("ぁ", "a", "a"), ("ぃ", "i", "i"), ("ぅ", "u", "u"), ("ぇ", "e", "e"), ("ぉ", "o", "o"),
+    ("ゃ", "ya", "ya"), ("ゅ", "yu", "yu"), ("ょ", "yo", "yo"), ("ゎ", "wa", "wa"),
+    // 訓令式の "ti" は ち
+    ("てぃ", "ti", "ti"), ("でぃ", "di", "di"),
+];
+
+// TABLEの綴り以外にローマ字 → かなで受け付けるもの
+const ROMAJI_ALIASES: &[(&str, &str)] = &[
+    ("wo", "を"), ("jya", "じゃ"), ("jyu", "じゅ"), ("jyo", "じょ"), ("cya", "ちゃ"), ("cyu", "ちゅ"), ("cyo", "ちょ"),
+];
+
+pub fn is_hiragana(c: char) -> bool {
+    matches!(c, '\u{3041}'..='\u{3096}')
+}
+
+pub fn is_katakana(c: char) -> bool {
+    matches!(c, '\u{30A1}'..='\u{30F6}')
+}
+
+// かな・長音符・中黒だけからなる
+pub fn is_kana(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| is_hiragana(c) || is_katakana(c) || c == 'ー' || c == '・')
+}
+
+pub fn to_hiragana(s: &str) -> String {
+    s.chars()
+        .map(|c| if is_katakana(c) { char::from_u32(c as u32 - 0x60).unwrap() } else { c })
+        .collect()
+}
+
+pub fn to_katakana(s: &str) -> String {
+    s.chars()
+        .map(|c| if is_hiragana(c) { char::from_u32(c as u32 + 0x60).unwrap() } else { c })
+        .collect()
+}
+
+// かな以外の文字はそのまま残す
+pub fn to_romaji(s: &str, system: Romaji) -> String {
+    let hira: Vec<char> = to_hiragana(s).chars().collect();
+    let syllable = |i: usize| -> Option<(usize, &'static str)> {
+        for len in [2, 1] {
+            let Some(v) = hira.get(i..i + len) else { continue };
+            let v: String = v.iter().collect();
+            if let Some((_, hep, kun)) = TABLE.iter().find(|(k, _, _)| *k == v) {
+                return Some((len, if system == Romaji::Hepburn { hep } else { kun }));
+            }
+        }
+        None
+    };
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < hira.len() {
+        match hira[i] {
+            'っ' => {
+                // 次の子音を重ねる。ヘボン式の "ch" の前は "t"
+                if let Some((_, next)) = syllable(i + 1) {
+                    match next.chars().next() {
+                        Some('c') => out.push('t'),
+                        Some(c) if !is_vowel(c) && c != 'n' => out.push(c),
+                        _ => {}
+                    }
+                }
+                i += 1;
+            }
+            'ん' => {
+                let next = syllable(i + 1).and_then(|(_, v)| v.chars().next());
+                match next {
+                    Some(c) if is_vowel(c) || c == 'y' => out.push_str("n'"),
+                    Some('b' | 'm' | 'p') if system == Romaji::Hepburn => out.push('m'),
+                    _ => out.push('n'),
+                }
+                i += 1;
+            }
+            'ー' => {
+                if let Some(v) = out.chars().next_back().filter(|c| is_vowel(*c)) {
+                    out.push(v);
+                }
+                i += 1;
+            }
+            c => match syllable(i) {
+                Some((len, v)) => {
+                    out.push_str(v);
+                    i += len;
+                }
+                None => {
+                    out.push(c);
+                    i += 1;
+                }
+            },
+        }
+    }
+    out
+}
+
+// ローマ字をひらがなにする。大文字・マクロン付きの母音 (ō → おう) も受け付ける
+pub fn from_romaji(s: &str) -> Result<String, KanaError> {
+    let lower: Vec<(usize, char)> = s.char_indices()
+        .flat_map(|(i, c)| {
+            let long = match c.to_lowercase().next().unwrap_or(c) {
+                'ā' | 'â' => "aa",
+                'ī' | 'î' => "ii",
+                'ū' | 'û' => "uu",
+                'ē' | 'ê' => "ee",
+                'ō' | 'ô' => "ou",
+                _ => "",
+            };
+            if long.is_empty() {
+                vec![(i, c.to_ascii_lowercase())]
+            } else {
+                long.chars().map(|v| (i, v)).collect()
+            }
+        })
+        .collect();
+    let tail = |i: usize| -> String { lower[i..].iter().map(|(_, c)| *c).collect() };
+
+    let mut out = String::new();
+    let mut i = 0;
+    while i < lower.len() {
+        let (offset, c) = lower[i];
+        let next = lower.get(i + 1).map(|v| v.1);
+        if c.is_whitespace() || c == '-' || c == '\'' {
+            if c.is_whitespace() {
+                out.push(c);
+            }
+            i += 1;
+            continue;
+        }
+        // 撥音: 母音・yの前以外のn、b/m/pの前のm
+        if (c == 'n' && !matches!(next, Some(v) if is_vowel(v) || v == 'y'))
+            || (c == 'm' && matches!(next, Some('b' | 'm' | 'p'))) {
+            out.push('ん');
+            i += 1;
+            continue;
+        }
+        // 促音: 同じ子音の重なり、"tch"
+        if !is_vowel(c) && (next == Some(c) || (c == 't' && next == Some('c'))) {
+            out.push('っ');
+            i += 1;
+            continue;
+        }
+        // 長音の "oh" (直後が母音でない)
+        if c == 'o' && next == Some('h') && !matches!(lower.get(i + 2), Some((_, v)) if is_vowel(*v)) {
+            out.push_str("おう");
+            i += 2;
+            continue;
+        }
+
+        let rest = tail(i);
+        let found = TABLE.iter()
+            .flat_map(|(k, hep, kun)| [(*hep, *k), (*kun, *k)])
+            .chain(ROMAJI_ALIASES.iter().copied())
+            .filter(|(r, _)| rest.starts_with(r))
+            .fold(None, |acc: Option<(&str, &str)>, v| match acc {
+                Some(a) if a.0.len() >= v.0.len() => Some(a),
+                _ => Some(v),
+            });
+        match found {
+            Some((r, k)) => {
+                out.push_str(k);
+                i += r.chars().count();
+            }
+            None => return Err(KanaError::Unexpected { offset, found: s[offset..].chars().next().unwrap() }),
+        }
+    }
+    Ok(out)
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'i' | 'u' | 'e' | 'o')
+}
+
+// 五十音順の比較キー。
+//   1. 濁点・半濁点を外し、小書きを大きくし、長音符を母音にしたもの (が → か、ゃ → や、ー → あ など)
+//   2. ひらがなにしたそのもの (清音 → 濁音 → 半濁音の順になる)
+// ひらがなの符号位置は清音だけ見れば五十音順に並んでいる
+pub fn gojuon_key(s: &str) -> (String, String) {
+    let hira = to_hiragana(s);
+    let mut primary = String::new();
+    let mut secondary = String::new();
+    for c in hira.chars() {
+        let c = if c == 'ー' { long_vowel(&secondary).unwrap_or(c) } else { c };
+        secondary.push(c);
+        primary.push(seion(c));
+    }
+    (primary, secondary)
+}
+
+pub fn gojuon_cmp(a: &str, b: &str) -> Ordering {
+    gojuon_key(a).cmp(&gojuon_key(b))
+}
+
+const DAKUON: &str = "がぎぐげござじずぜぞだぢづでどばびぶべぼぱぴぷぺぽゔぁぃぅぇぉっゃゅょゎゕゖ";
+const SEION: &str = "かきくけこさしすせそたちつてとはひふへほはひふへほうあいうえおつやゆよわかけ";
+
+fn seion(c: char) -> char {
+    DAKUON.chars().position(|v| v == c).and_then(|i| SEION.chars().nth(i)).unwrap_or(c)
+}
+
+// 直前のかなの母音
+fn long_vowel(s: &str) -> Option<char> {
+    let last = s.chars().next_back()?;
+    match to_romaji(&last.to_string(), Romaji::Hepburn).chars().next_back()? {
+        'a' => Some('あ'),
+        'i' => Some('い'),
+        'u' => Some('う'),
+        'e' => Some('え'),
+        'o' => Some('お'),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::name::kana::*;
+
+    #[test]
+    // 目的：かなからヘボン式・訓令式のローマ字にできるかを確認する
+    fn test_to_romaji() {
+        assert_eq!(to_romaji("はやし りゅうた", Romaji::Hepburn), "hayashi ryuuta");
+        assert_eq!(to_romaji("ハヤシ リュウタ", Romaji::Kunrei), "hayasi ryuuta");
+        assert_eq!(to_romaji("まっちゃ", Romaji::Hepburn), "matcha");
+        assert_eq!(to_romaji("まっちゃ", Romaji::Kunrei), "mattya");
+        assert_eq!(to_romaji("しんいち", Romaji::Hepburn), "shin'ichi");
+        assert_eq!(to_romaji("なんば", Romaji::Hepburn), "namba");
+        assert_eq!(to_romaji("なんば", Romaji::Kunrei), "nanba");
+        assert_eq!(to_romaji("ケーコ", Romaji::Hepburn), "keeko");
+        assert_eq!(to_katakana("さとう"), "サトウ");
+        assert_eq!(to_hiragana("サトウ・ケイ"), "さとう・けい");
+    }
+
+    #[test]
+    // 目的：ヘボン式・訓令式どちらのローマ字もかなに戻せるかを確認する
+    fn test_from_romaji() {
+        assert_eq!(from_romaji("Hayashi").unwrap(), "はやし");
+        assert_eq!(from_romaji("hayasi").unwrap(), "はやし");
+        assert_eq!(from_romaji("Ryūta").unwrap(), "りゅうた");
+        assert_eq!(from_romaji("matcha").unwrap(), "まっちゃ");
+        assert_eq!(from_romaji("shin'ichi").unwrap(), "しんいち");
+        assert_eq!(from_romaji("Namba").unwrap(), from_romaji("Nanba").unwrap());
+        assert_eq!(from_romaji("Ohno").unwrap(), "おうの");
+        assert_eq!(from_romaji("Smith"), Err(KanaError::Unexpected { offset: 0, found: 'S' }));
+        for v in ["はやし", "りゅうた", "まっちゃ", "しんいち", "ふじもと"] {
+            for system in [Romaji::Hepburn, Romaji::Kunrei] {
+                assert_eq!(from_romaji(&to_romaji(v, system)).unwrap(), v);
+            }
+        }
+    }
+
+    #[test]
+    // 目的：五十音順 (清音が先、濁点・小書き・長音符を考慮) に並ぶかを確認する
+    fn test_gojuon_cmp() {
+        let mut v = vec!["はやし", "ばば", "さとう", "アイザワ", "はば", "ぱぱ", "いとう", "サトー"];
+        v.sort_by(|a, b| gojuon_cmp(a, b));
+        assert_eq!(v, vec!["アイザワ", "いとう", "さとう", "サトー", "はば", "ばば", "ぱぱ", "はやし"]);
+    }
+}
@@ -0,0 +1,852 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use thiserror::Error;
+
+pub mod dump;
+pub mod filter;
+pub mod lexer;
+pub mod object;
+pub mod objstm;
+pub mod repair;
+pub mod xref;
+
+use object::{Dict, IndirectObject, ObjRef, Object};
+use repair::{RepairReason, RepairReport};
+
+// 仮想的なPDFのデータ構造。
+// Docは元のバイト列を借用し、XRefTableの各エントリも元のバイト列の該当行を指す。
+// OwnedDocはバイト列を自分で持ち、エントリやtrailerは複製して持つ。docでそれを借用するDocが作れる。
+// 追記更新されたファイルは、末尾のxrefからtrailerの/Prevをたどって古いxrefを順に読み、新しい方を優先して合わせる。
+// xrefは従来の表と相互参照ストリームのどちらでもよく、オブジェクトストリームの中のオブジェクトも解決できる。
+// ストリームの中身はfilterモジュールで展開する。
+// xrefが壊れているファイルは、Doc::repairで本体を走査してxrefを作り直せる (repairモジュール)。
+// DocのDebugでは元のバイト列を長さだけにする。中身をまとめて見るにはDoc::dumpを使う。
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PdfError {
+    #[error("startxref not found")]
+    StartXRefNotFound,
+    #[error("invalid startxref value at offset {offset}")]
+    BadStartXRef { offset: usize },
+    #[error("xref section not found at offset {offset}")]
+    XRefNotFound { offset: usize },
+    #[error("malformed xref subsection header at offset {offset}")]
+    BadSubsection { offset: usize },
+    #[error("malformed xref entry at offset {offset}")]
+    BadEntry { offset: usize },
+    #[error("unexpected end of data at offset {offset}")]
+    UnexpectedEof { offset: usize },
+    #[error("unexpected token at offset {offset}")]
+    UnexpectedToken { offset: usize },
+    #[error("malformed trailer at offset {offset}")]
+    BadTrailer { offset: usize },
+    #[error("/Prev chain loops back to xref at offset {offset}")]
+    PrevLoop { offset: usize },
+    #[error("malformed object header at offset {offset}")]
+    BadObjectHeader { offset: usize },
+    #[error("object {expected:?} expected at offset {offset} but found {found:?}")]
+    ObjectMismatch { expected: ObjRef, found: ObjRef, offset: usize },
+    #[error("malformed cross-reference stream at offset {offset}")]
+    BadXRefStream { offset: usize },
+    #[error("malformed object stream at offset {offset}")]
+    BadObjectStream { offset: usize },
+    #[error("unsupported filter {filter} for stream at offset {offset}")]
+    UnsupportedFilter { filter: String, offset: usize },
+    #[error("{filter} failed for stream at offset {offset}: {source}")]
+    Filter { filter: String, offset: usize, source: filter::FilterError },
+    #[error("no objects found while repairing xref")]
+    NoObjectsFound,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XRefEntryKind {
+    // 空き。nextは次の空きオブジェクト番号
+    Free { next: u64 },
+    // 使用中。offsetはファイル先頭からの "n g obj" の位置
+    InUse { offset: usize },
+    // オブジェクトストリームのindex番目
+    Compressed { stream: u32, index: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XRefTableEntry<'doc> {
+    pub obj_num: u32,
+    pub gen: u16,
+    pub kind: XRefEntryKind,
+    // このエントリの行。従来の表なら元のバイト列の20バイト、相互参照ストリームなら展開後の1行
+    pub entry_data: Cow<'doc, [u8]>,
+}
+
+impl<'doc> XRefTableEntry<'doc> {
+    pub fn new(obj_num: u32, gen: u16, kind: XRefEntryKind, data: Cow<'doc, [u8]>) -> Self {
+        XRefTableEntry { obj_num, gen, kind, entry_data: data }
+    }
+
+    // PDFの相互参照ストリームでの種別 (0: 空き, 1: 使用中, 2: オブジェクトストリーム内)
+    pub fn entry_type(&self) -> i32 {
+        match self.kind {
+            XRefEntryKind::Free { .. } => 0,
+            XRefEntryKind::InUse { .. } => 1,
+            XRefEntryKind::Compressed { .. } => 2,
+        }
+    }
+
+    pub fn into_owned(self) -> XRefTableEntry<'static> {
+        XRefTableEntry { entry_data: Cow::Owned(self.entry_data.into_owned()), ..self }
+    }
+}
+
+// trailer辞書。/Prevをたどった場合は古いものから順に重ね、/Prevと相互参照ストリーム自体のキーを除いたもの
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Trailer<'a> {
+    dict: Dict<'a>,
+}
+
+impl<'a> Trailer<'a> {
+    pub fn new(dict: Dict<'a>) -> Self {
+        Trailer { dict }
+    }
+
+    pub fn dict(&self) -> &Dict<'a> {
+        &self.dict
+    }
+
+    pub fn size(&self) -> Option<u32> {
+        self.dict.get("Size")?.as_int()?.try_into().ok()
+    }
+
+    pub fn root(&self) -> Option<ObjRef> {
+        self.dict.get("Root")?.as_reference()
+    }
+
+    pub fn info(&self) -> Option<ObjRef> {
+        self.dict.get("Info")?.as_reference()
+    }
+
+    // ファイル識別子 (最初の版, 今の版)
+    pub fn id(&self) -> Option<(&[u8], &[u8])> {
+        match self.dict.get("ID")?.as_array()? {
+            [a, b] => Some((a.as_bytes()?, b.as_bytes()?)),
+            _ => None,
+        }
+    }
+
+    pub fn prev(&self) -> Option<&Object<'a>> {
+        self.dict.get("Prev")
+    }
+
+    pub fn into_owned(self) -> Trailer<'static> {
+        Trailer { dict: self.dict.into_owned() }
+    }
+
+    // 古い順に重ね、xrefの読み方にだけ関わるキーを除く
+    fn merged(dicts: impl IntoIterator<Item = Dict<'a>>) -> Self {
+        let mut trailer = Trailer::default();
+        for d in dicts {
+            for (k, v) in d.entries() {
+                trailer.dict.insert(k, v);
+            }
+        }
+        for key in ["Prev", "XRefStm", "Type", "W", "Index", "Length", "Filter", "DecodeParms"] {
+            trailer.dict.remove(key);
+        }
+        trailer
+    }
+}
+
+#[derive(Debug)]
+pub struct XRefTable<'doc> {
+    // オブジェクト番号の昇順
+    table_data: Vec<XRefTableEntry<'doc>>,
+    trailer: Trailer<'doc>,
+    // 展開したオブジェクトストリーム。ストリームのオブジェクト番号ごと。
+    // OwnedDocをArcで複数のスレッドから読めるようにMutexにする
+    objstm: Mutex<BTreeMap<u32, Arc<objstm::Decoded>>>,
+    // 読んだxrefの位置。新しいものから
+    sections: Vec<usize>,
+}
+
+// 展開したオブジェクトストリームは複製せずに共有する
+impl Clone for XRefTable<'_> {
+    fn clone(&self) -> Self {
+        XRefTable {
+            table_data: self.table_data.clone(),
+            trailer: self.trailer.clone(),
+            objstm: Mutex::new(self.objstm_cache().clone()),
+            sections: self.sections.clone(),
+        }
+    }
+}
+
+impl<'doc> XRefTable<'doc> {
+    pub fn new(dat: &'doc [u8]) -> Result<Self, PdfError> {
+        let mut merged: BTreeMap<u32, XRefTableEntry<'doc>> = BTreeMap::new();
+        let mut trailers = vec![];
+        let mut sections = vec![];
+        let mut next = Some(xref::find_startxref(dat)?);
+        while let Some(offset) = next {
+            if sections.contains(&offset) {
+                return Err(PdfError::PrevLoop { offset });
+            }
+            sections.push(offset);
+            // startxrefと違い、/Prevの値はファイルの中を指しているとは限らない
+            let head = dat.get(offset..).ok_or(PdfError::XRefNotFound { offset })?;
+            let (entries, dict, trailer_offset) = if head.starts_with(b"xref") {
+                let section = xref::parse_xref_section(dat, offset)?;
+                let dict = xref::parse_trailer(dat, section.trailer_offset)?;
+                // 併用型のファイル: /XRefStm の相互参照ストリームは表に無い番号を補う。
+                // 古い読み手向けに表で空きにしてある番号もストリームの方を使う
+                let mut entries = vec![];
+                if let Some(v) = dict.get("XRefStm") {
+                    let at = v.as_int().and_then(|v| usize::try_from(v).ok())
+                        .ok_or(PdfError::BadTrailer { offset: section.trailer_offset })?;
+                    entries = xref::parse_xref_stream(dat, at)?.0;
+                }
+                let hidden: Vec<u32> = entries.iter().map(|e| e.obj_num).collect();
+                entries.extend(section.entries.into_iter()
+                    .filter(|e| !(matches!(e.kind, XRefEntryKind::Free { .. }) && hidden.contains(&e.obj_num))));
+                (entries, dict, section.trailer_offset)
+            } else {
+                let (entries, dict) = xref::parse_xref_stream(dat, offset)?;
+                (entries, dict, offset)
+            };
+            let trailer = Trailer::new(dict);
+
+            // 同じxrefの中で重複していれば後のもの、xref同士では新しいものを使う
+            let mut this: BTreeMap<u32, XRefTableEntry<'doc>> = BTreeMap::new();
+            for e in entries {
+                this.insert(e.obj_num, e);
+            }
+            for (num, e) in this {
+                merged.entry(num).or_insert(e);
+            }
+
+            next = match trailer.prev() {
+                None => None,
+                Some(v) => match v.as_int().and_then(|v| usize::try_from(v).ok()) {
+                    Some(v) => Some(v),
+                    None => return Err(PdfError::BadTrailer { offset: trailer_offset }),
+                },
+            };
+            trailers.push(trailer);
+        }
+
+        let trailer = Trailer::merged(trailers.into_iter().rev().map(|t| t.dict));
+        Ok(XRefTable { table_data: merged.into_values().collect(), trailer, objstm: Mutex::default(), sections })
+    }
+
+    // newで読み、repairなら読めない・オフセットがずれている場合に走査して作り直す
+    fn load(dat: &'doc [u8], repair: bool) -> Result<(Self, Option<RepairReport>), PdfError> {
+        let reason = match XRefTable::new(dat) {
+            Ok(t) if !repair => return Ok((t, None)),
+            Ok(t) => {
+                let bad = repair::bad_offsets(dat, t.entries());
+                if bad.is_empty() {
+                    return Ok((t, None));
+                }
+                RepairReason::BadOffsets(bad)
+            }
+            Err(e) if !repair => return Err(e),
+            Err(e) => RepairReason::Unreadable(e),
+        };
+        let (t, report) = XRefTable::scan(dat, reason)?;
+        Ok((t, Some(report)))
+    }
+
+    // 本体を走査して作り直す。sectionsは空になる
+    fn scan(dat: &'doc [u8], reason: RepairReason) -> Result<(Self, RepairReport), PdfError> {
+        let (table_data, trailers, report) = repair::scan(dat, reason)?;
+        let mut trailer = Trailer::merged(trailers);
+        // /Sizeは見つけた最大の番号+1より小さくしない
+        let size = table_data.last().map_or(0, |e| e.obj_num as i64 + 1);
+        if trailer.dict.get("Size").and_then(Object::as_int).is_none_or(|v| v < size) {
+            trailer.dict.insert(Cow::Borrowed(b"Size"), Object::Integer(size));
+        }
+        Ok((XRefTable { table_data, trailer, objstm: Mutex::default(), sections: vec![] }, report))
+    }
+
+    pub fn entries(&self) -> &[XRefTableEntry<'doc>] {
+        &self.table_data
+    }
+
+    pub fn get(&self, obj_num: u32) -> Option<&XRefTableEntry<'doc>> {
+        let i = self.table_data.binary_search_by_key(&obj_num, |v| v.obj_num).ok()?;
+        Some(&self.table_data[i])
+    }
+
+    pub fn trailer(&self) -> &Trailer<'doc> {
+        &self.trailer
+    }
+
+    pub fn sections(&self) -> &[usize] {
+        &self.sections
+    }
+
+    // 展開中にpanicしても、入っているのは展開し終えたものだけなので使い続ける
+    fn objstm_cache(&self) -> MutexGuard<'_, BTreeMap<u32, Arc<objstm::Decoded>>> {
+        self.objstm.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // 借用をやめて複製したもの
+    pub fn into_owned(self) -> XRefTable<'static> {
+        XRefTable {
+            table_data: self.table_data.into_iter().map(XRefTableEntry::into_owned).collect(),
+            trailer: self.trailer.into_owned(),
+            objstm: self.objstm,
+            sections: self.sections,
+        }
+    }
+}
+
+// 元のバイト列を借用する。xrefのエントリやtrailerも元のバイト列を指す。
+// OwnedDoc::docで作ったものは、OwnedDocが持つxrefを借用する。
+pub struct Doc<'a> {
+    dat: &'a [u8],
+    table: Option<Cow<'a, XRefTable<'a>>>,
+    // repairでxrefを作り直した場合だけ
+    repair: Option<Cow<'a, RepairReport>>,
+}
+
+// 元のバイト列は長さだけ
+impl fmt::Debug for Doc<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Doc")
+            .field("dat", &format_args!("[{} bytes]", self.dat.len()))
+            .field("table", &self.table)
+            .field("repair", &self.repair)
+            .finish()
+    }
+}
+
+impl<'a> Doc<'a> {
+    pub fn new(dat: &'a [u8]) -> Result<Self, PdfError> {
+        Doc::load(dat, false)
+    }
+
+    // newと同じだが、xrefが読めない・オフセットが見出しを指していない場合は本体を走査して作り直す。
+    // 作り直したかどうかと、その内容はrepair_reportで分かる
+    pub fn repair(dat: &'a [u8]) -> Result<Self, PdfError> {
+        Doc::load(dat, true)
+    }
+
+    fn load(dat: &'a [u8], repair: bool) -> Result<Self, PdfError> {
+        let (table, report) = XRefTable::load(dat, repair)?;
+        let mut d = Doc { dat, table: Some(Cow::Owned(table)), repair: None };
+        let Some(mut report) = report else {
+            return Ok(d);
+        };
+
+        // /Rootが無ければ、最後に見つかったカタログを使う
+        if d.trailer().and_then(Trailer::root).is_none() {
+            let root = d.table().unwrap().entries().iter()
+                .map(|e| ObjRef { num: e.obj_num, gen: e.gen })
+                .rfind(|r| {
+                    let o = d.object(*r).unwrap_or(Object::Null);
+                    o.as_dict().and_then(|v| v.get("Type")).and_then(Object::as_name) == Some(b"Catalog")
+                });
+            if let (Some(r), Some(t)) = (root, d.table.as_mut()) {
+                t.to_mut().trailer.dict.insert(Cow::Borrowed(b"Root"), Object::Ref(r));
+                report.root_guessed = true;
+            }
+        }
+        d.repair = Some(Cow::Owned(report));
+        Ok(d)
+    }
+
+    pub fn repair_report(&self) -> Option<&RepairReport> {
+        self.repair.as_deref()
+    }
+
+    // trailer・xref・オブジェクトの参照関係を字下げした木にしたもの (dumpモジュール)
+    pub fn dump(&self) -> String {
+        dump::tree(self)
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.dat
+    }
+
+    pub fn table(&self) -> Option<&XRefTable<'a>> {
+        self.table.as_deref()
+    }
+
+    pub fn trailer(&self) -> Option<&Trailer<'a>> {
+        Some(self.table()?.trailer())
+    }
+
+    // 参照先のオブジェクト。xrefに無い番号・空きの番号・世代が違うものはnull
+    pub fn object(&self, r: ObjRef) -> Result<Object<'a>, PdfError> {
+        Ok(self.indirect(r)?.map_or(Object::Null, |v| v.obj))
+    }
+
+    // 参照なら参照先を、それ以外はそのまま返す。参照先がまた参照ならたどる
+    pub fn resolve(&self, obj: &Object<'a>) -> Result<Object<'a>, PdfError> {
+        let mut obj = obj.clone();
+        // 参照の循環で止まらないように
+        for _ in 0..32 {
+            match obj {
+                Object::Ref(r) => obj = self.object(r)?,
+                _ => return Ok(obj),
+            }
+        }
+        Ok(Object::Null)
+    }
+
+    // trailerの/Rootが指す文書カタログ
+    pub fn catalog(&self) -> Result<Object<'a>, PdfError> {
+        match self.trailer().and_then(Trailer::root) {
+            Some(r) => self.object(r),
+            None => Ok(Object::Null),
+        }
+    }
+
+    fn indirect(&self, r: ObjRef) -> Result<Option<IndirectObject<'a>>, PdfError> {
+        let Some(e) = self.table().and_then(|t| t.get(r.num)) else {
+            return Ok(None);
+        };
+        match e.kind {
+            XRefEntryKind::InUse { offset } if e.gen == r.gen => {
+                let o = object::parse_indirect(self.dat, offset, &|r| self.length(r))?;
+                if o.id != r {
+                    return Err(PdfError::ObjectMismatch { expected: r, found: o.id, offset });
+                }
+                Ok(Some(o))
+            }
+            XRefEntryKind::Compressed { stream, index } if r.gen == 0 => {
+                let Some(t) = self.table() else {
+                    return Ok(None);
+                };
+                // オブジェクトストリームは番号ごとに覚えておく。
+                // 展開の間はロックを持たないので、複数のスレッドが同時に展開した場合は先に入れた方を使う
+                let cached = t.objstm_cache().get(&stream).cloned();
+                let decoded = match cached {
+                    Some(v) => v,
+                    None => {
+                        // オブジェクトストリーム自体はオブジェクトストリームに入らない
+                        let Some(XRefEntryKind::InUse { offset }) = t.get(stream).map(|e| e.kind) else {
+                            return Ok(None);
+                        };
+                        let stm = object::parse_indirect(self.dat, offset, &|r| self.length(r))?;
+                        let Object::Stream(stm) = stm.obj else {
+                            return Err(PdfError::BadObjectStream { offset });
+                        };
+                        let decoded = Arc::new(objstm::decode(&stm)?);
+                        t.objstm_cache().entry(stream).or_insert(decoded).clone()
+                    }
+                };
+                let o = decoded.get(self.dat, index)?;
+                if o.id != r {
+                    return Err(PdfError::ObjectMismatch { expected: r, found: o.id, offset: decoded.offset() });
+                }
+                Ok(Some(o))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // ストリームの /Length が間接参照の場合の値
+    fn length(&self, r: ObjRef) -> Option<i64> {
+        match self.table()?.get(r.num)?.kind {
+            XRefEntryKind::InUse { offset } => object::parse_indirect(self.dat, offset, &|_| None).ok()?.obj.as_int(),
+            _ => None,
+        }
+    }
+}
+
+// 元のバイト列を自分で持つDoc (from_vec, open)。xrefのエントリやtrailerも複製して持つので、
+// 関数から返したり他のスレッドに渡したりできる。読み出しはdocで作った借用のDocを通す。
+pub struct OwnedDoc {
+    dat: Vec<u8>,
+    table: Option<XRefTable<'static>>,
+    repair: Option<RepairReport>,
+}
+
+// 元のバイト列は長さだけ
+impl fmt::Debug for OwnedDoc {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedDoc")
+            .field("dat", &format_args!("[{} bytes]", self.dat.len()))
+            .field("table", &self.table)
+            .field("repair", &self.repair)
+            .finish()
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum OpenError {
+    #[error("cannot read {path}: {source}")]
+    Io { path: PathBuf, source: io::Error },
+    #[error(transparent)]
+    Pdf(#[from] PdfError),
+}
+
+impl OwnedDoc {
+    pub fn from_vec(dat: Vec<u8>) -> Result<Self, PdfError> {
+        OwnedDoc::load(dat, false)
+    }
+
+    // from_vecと同じだが、Doc::repairと同じく壊れたxrefを作り直す
+    pub fn repair_vec(dat: Vec<u8>) -> Result<Self, PdfError> {
+        OwnedDoc::load(dat, true)
+    }
+
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, OpenError> {
+        let path = path.as_ref();
+        let dat = fs::read(path).map_err(|source| OpenError::Io { path: path.to_path_buf(), source })?;
+        Ok(OwnedDoc::from_vec(dat)?)
+    }
+
+    fn load(dat: Vec<u8>, repair: bool) -> Result<Self, PdfError> {
+        let (table, report) = {
+            let d = Doc::load(&dat, repair)?;
+            (d.table.map(|t| t.into_owned().into_owned()), d.repair.map(Cow::into_owned))
+        };
+        Ok(OwnedDoc { dat, table, repair: report })
+    }
+
+    // 持っているバイト列とxrefを借用するDoc
+    pub fn doc(&self) -> Doc<'_> {
+        let table: Option<&XRefTable<'_>> = self.table.as_ref();
+        Doc { dat: &self.dat, table: table.map(Cow::Borrowed), repair: self.repair.as_ref().map(Cow::Borrowed) }
+    }
+
+    pub fn repair_report(&self) -> Option<&RepairReport> {
+        self.repair.as_ref()
+    }
+
+    pub fn dump(&self) -> String {
+        self.doc().dump()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.dat
+    }
+
+    pub fn table(&self) -> Option<&XRefTable<'_>> {
+        self.table.as_ref()
+    }
+
+    pub fn trailer(&self) -> Option<&Trailer<'_>> {
+        Some(self.table.as_ref()?.trailer())
+    }
+
+    pub fn object(&self, r: ObjRef) -> Result<Object<'_>, PdfError> {
+        self.doc().object(r)
+    }
+
+    pub fn resolve<'s>(&'s self, obj: &Object<'s>) -> Result<Object<'s>, PdfError> {
+        self.doc().resolve(obj)
+    }
+
+    pub fn catalog(&self) -> Result<Object<'_>, PdfError> {
+        self.doc().catalog()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::pdf::*;
+
+    // オブジェクトの本体を並べて、正しいオフセットのxrefとtrailerを付けたPDFを作る
+    pub(crate) fn build_pdf(objs: &[&str]) -> Vec<u8> {
+        let mut v = b"%PDF-1.4\n".to_vec();
+        let mut offsets = vec![];
+        for (i, body) in objs.iter().enumerate() {
+            offsets.push(v.len());
+            v.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", i + 1, body).as_bytes());
+        }
+        let start = v.len();
+        v.extend_from_slice(format!("xref\n0 {}\n0000000000 65535 f\r\n", objs.len() + 1).as_bytes());
+        for offset in offsets {
+            v.extend_from_slice(format!("{:010} 00000 n\r\n", offset).as_bytes());
+        }
+        v.extend_from_slice(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objs.len() + 1, start).as_bytes());
+        v
+    }
+
+    // 追記更新を足す。objsは (オブジェクト番号, 本体)、extraはtrailerに足すもの
+    pub(crate) fn append_update(v: &mut Vec<u8>, objs: &[(u32, &str)], extra: &str) {
+        let prev = crate::pdf::xref::find_startxref(v).unwrap();
+        let mut entries = vec![];
+        for (num, body) in objs {
+            entries.push((*num, v.len()));
+            v.extend_from_slice(format!("{} 0 obj\n{}\nendobj\n", num, body).as_bytes());
+        }
+        let start = v.len();
+        v.extend_from_slice(b"xref\n");
+        for (num, offset) in &entries {
+            v.extend_from_slice(format!("{} 1\n{:010} 00000 n\r\n", num, offset).as_bytes());
+        }
+        let size = entries.iter().map(|v| v.0 + 1).max().unwrap_or(0);
+        v.extend_from_slice(format!("trailer\n<< /Size {} /Prev {} {} >>\nstartxref\n{}\n%%EOF\n", size, prev, extra, start).as_bytes());
+    }
+
+    // 相互参照ストリームとオブジェクトストリームを使ったPDFを作る。
+    // 1: カタログ (直接)、2: オブジェクトストリーム (3, 4 を含む)、5: 相互参照ストリーム
+    pub(crate) fn build_pdf_with_streams() -> Vec<u8> {
+        let mut v = b"%PDF-1.5\n".to_vec();
+        let o1 = v.len();
+        v.extend_from_slice(b"1 0 obj\n<< /Type /Catalog /Pages 3 0 R >>\nendobj\n");
+
+        let objs = ["<< /Type /Pages /Kids [] /Count 0 >>", "(in stream)"];
+        let (mut header, mut body) = (String::new(), String::new());
+        for (i, o) in objs.iter().enumerate() {
+            header += &format!("{} {} ", i + 3, body.len());
+            body += o;
+            body += " ";
+        }
+        let o2 = v.len();
+        v.extend_from_slice(format!("2 0 obj\n<< /Type /ObjStm /N 2 /First {} /Length {} >>\nstream\n{}{}\nendstream\nendobj\n",
+            header.len(), header.len() + body.len(), header, body).as_bytes());
+
+        let o5 = v.len();
+        let mut rows = vec![];
+        for (ty, f1, f2) in [(0u8, 0u32, 0xffffu16), (1, o1 as u32, 0), (1, o2 as u32, 0), (2, 2, 0), (2, 2, 1), (1, o5 as u32, 0)] {
+            rows.push(ty);
+            rows.extend_from_slice(&f1.to_be_bytes());
+            rows.extend_from_slice(&f2.to_be_bytes());
+        }
+        v.extend_from_slice(format!("5 0 obj\n<< /Type /XRef /Size 6 /W [1 4 2] /Root 1 0 R /Length {} >>\nstream\n", rows.len()).as_bytes());
+        v.extend_from_slice(&rows);
+        v.extend_from_slice(format!("\nendstream\nendobj\nstartxref\n{}\n%%EOF\n", o5).as_bytes());
+        v
+    }
+
+    #[test]
+    // 目的：startxrefからxrefを読み、使用中・空きのエントリが作られるかを確認する
+    fn test_doc_new() {
+        let dat = build_pdf(&["<< /Type /Catalog >>", "(hello)"]);
+        let d = Doc::new(&dat).unwrap();
+        let tbl = d.table().unwrap();
+        assert_eq!(tbl.entries().len(), 3);
+        assert_eq!(tbl.get(0).unwrap().kind, XRefEntryKind::Free { next: 0 });
+        assert_eq!(tbl.get(0).unwrap().entry_type(), 0);
+        let e = tbl.get(2).unwrap();
+        assert_eq!(e.entry_type(), 1);
+        let XRefEntryKind::InUse { offset } = e.kind else { panic!() };
+        assert!(dat[offset..].starts_with(b"2 0 obj"));
+        assert_eq!(e.entry_data, format!("{:010} 00000 n\r\n", offset).as_bytes());
+        assert!(tbl.get(3).is_none());
+        // Debugでは元のバイト列は長さだけ
+        let s = format!("{:?}", d);
+        assert!(s.starts_with(&format!("Doc {{ dat: [{} bytes], table: Some(XRefTable {{ table_data: [", dat.len())));
+        assert!(s.ends_with(&format!("sections: {:?} }}), repair: None }}", tbl.sections())));
+    }
+
+    #[test]
+    // 目的：startxrefがない・指す先がxrefでない場合にエラーになるかを確認する
+    fn test_doc_new_error() {
+        assert_eq!(Doc::new(b"%PDF-1.4\n").err(), Some(PdfError::StartXRefNotFound));
+        let dat = b"%PDF-1.4\nstartxref\n3\n%%EOF";
+        assert_eq!(Doc::new(dat).err(), Some(PdfError::XRefNotFound { offset: 3 }));
+        let dat = b"%PDF-1.4\nstartxref\n999\n%%EOF";
+        assert_eq!(Doc::new(dat).err(), Some(PdfError::BadStartXRef { offset: 19 }));
+    }
+
+    #[test]
+    // 目的：/Prevをたどり、新しいxrefのエントリとtrailerが優先されるかを確認する
+    fn test_doc_prev_chain() {
+        let mut dat = build_pdf(&["<< /Type /Catalog >>", "(old)"]);
+        append_update(&mut dat, &[(2, "(new)"), (3, "<< /Title (t) >>")], "/Info 3 0 R /ID [<01> <02>]");
+        let d = Doc::new(&dat).unwrap();
+        let tbl = d.table().unwrap();
+        assert_eq!(tbl.sections().len(), 2);
+        assert_eq!(tbl.entries().len(), 4);
+        let offset = |num| match tbl.get(num).unwrap().kind {
+            XRefEntryKind::InUse { offset } => offset,
+            _ => panic!(),
+        };
+        assert!(dat[offset(2)..].starts_with(b"2 0 obj\n(new)"));
+        assert!(dat[offset(1)..].starts_with(b"1 0 obj\n<< /Type /Catalog >>"));
+
+        let t = d.trailer().unwrap();
+        assert_eq!(t.size(), Some(4));
+        assert_eq!(t.root(), Some(ObjRef { num: 1, gen: 0 }));
+        assert_eq!(t.info(), Some(ObjRef { num: 3, gen: 0 }));
+        assert_eq!(t.id(), Some((&b"\x01"[..], &b"\x02"[..])));
+        assert!(t.prev().is_none());
+    }
+
+    #[test]
+    // 目的：xrefのオフセットを通してオブジェクトを解決できるかを確認する
+    fn test_doc_object() {
+        let dat = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R >>",
+            "<< /Kids [] /Count 0 >>",
+            "<< /Length 4 0 R >>\nstream\nabcdef\nendstream",
+            "6",
+        ]);
+        let d = Doc::new(&dat).unwrap();
+        let catalog = d.catalog().unwrap();
+        let pages = d.resolve(catalog.as_dict().unwrap().get("Pages").unwrap()).unwrap();
+        assert_eq!(pages.as_dict().unwrap().get("Count"), Some(&Object::Integer(0)));
+        let s = d.object(ObjRef { num: 3, gen: 0 }).unwrap();
+        assert_eq!(&*s.as_stream().unwrap().data, b"abcdef");
+        // 無い番号・世代が違うものはnull
+        assert_eq!(d.object(ObjRef { num: 9, gen: 0 }), Ok(Object::Null));
+        assert_eq!(d.object(ObjRef { num: 2, gen: 1 }), Ok(Object::Null));
+        assert_eq!(d.resolve(&Object::Integer(5)), Ok(Object::Integer(5)));
+        // 取り出したオブジェクトは元のバイト列を借用するので、Docより長く使える
+        let pages = {
+            let d = Doc::new(&dat).unwrap();
+            d.resolve(&Object::Ref(ObjRef { num: 2, gen: 0 })).unwrap()
+        };
+        assert_eq!(pages.as_dict().unwrap().get("Count"), Some(&Object::Integer(0)));
+
+        // xrefが別のオブジェクトを指している
+        let dat = String::from_utf8(dat).unwrap().replace("2 0 obj", "7 0 obj");
+        let d = Doc::new(dat.as_bytes()).unwrap();
+        assert!(matches!(d.object(ObjRef { num: 2, gen: 0 }),
+            Err(PdfError::ObjectMismatch { found: ObjRef { num: 7, gen: 0 }, .. })));
+    }
+
+    #[test]
+    // 目的：相互参照ストリームを読み、オブジェクトストリーム内のオブジェクトが解決できるかを確認する
+    fn test_doc_xref_stream() {
+        let dat = build_pdf_with_streams();
+        let d = Doc::new(&dat).unwrap();
+        let tbl = d.table().unwrap();
+        assert_eq!(tbl.get(4).unwrap().kind, XRefEntryKind::Compressed { stream: 2, index: 1 });
+        let t = d.trailer().unwrap();
+        assert_eq!((t.size(), t.root()), (Some(6), Some(ObjRef { num: 1, gen: 0 })));
+        assert!(t.dict().get("W").is_none());
+
+        let pages = d.resolve(d.catalog().unwrap().as_dict().unwrap().get("Pages").unwrap()).unwrap();
+        assert_eq!(pages.as_dict().unwrap().get("Count"), Some(&Object::Integer(0)));
+        assert_eq!(d.object(ObjRef { num: 4, gen: 0 }).unwrap().as_bytes(), Some(&b"in stream"[..]));
+        assert_eq!(d.object(ObjRef { num: 4, gen: 1 }), Ok(Object::Null));
+        // 3と4は同じオブジェクトストリームにあり、展開は1回だけ
+        assert_eq!(d.table().unwrap().objstm_cache().keys().collect::<Vec<_>>(), vec![&2]);
+
+        // 従来の表に /XRefStm を併用した追記
+        let mut dat = dat;
+        let stm_at = crate::pdf::xref::find_startxref(&dat).unwrap();
+        append_update(&mut dat, &[(3, "<< /Type /Pages /Kids [] /Count 1 >>")], &format!("/XRefStm {}", stm_at));
+        let d = Doc::new(&dat).unwrap();
+        assert_eq!(d.object(ObjRef { num: 3, gen: 0 }).unwrap().as_dict().unwrap().get("Count"), Some(&Object::Integer(1)));
+        assert_eq!(d.object(ObjRef { num: 4, gen: 0 }).unwrap().as_bytes(), Some(&b"in stream"[..]));
+    }
+
+    #[test]
+    // 目的：バイト列を自分で持つDocが、関数から返したり他のスレッドに渡したりしても使えるかを確認する
+    fn test_doc_open() {
+        // Arcで複数のスレッドから共有できる
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<OwnedDoc>();
+        assert_send_sync::<Doc<'static>>();
+        fn load(dat: &[u8]) -> OwnedDoc {
+            // 元のバイト列は関数を抜けると無くなる
+            OwnedDoc::from_vec(dat.to_vec()).unwrap()
+        }
+        let dat = build_pdf_with_streams();
+        let d = load(&dat);
+        let h = std::thread::spawn(move || d.object(ObjRef { num: 4, gen: 0 }).unwrap().into_owned());
+        assert_eq!(h.join().unwrap().as_bytes(), Some(&b"in stream"[..]));
+
+        // 1つのOwnedDocを複数のスレッドで読む。オブジェクトストリームの展開は共有される
+        let shared = Arc::new(load(&dat));
+        let hs: Vec<_> = (0..4).map(|_| {
+            let d = shared.clone();
+            std::thread::spawn(move || d.object(ObjRef { num: 4, gen: 0 }).unwrap().as_bytes().map(<[u8]>::to_vec))
+        }).collect();
+        for h in hs {
+            assert_eq!(h.join().unwrap(), Some(b"in stream".to_vec()));
+        }
+        assert_eq!(shared.table().unwrap().objstm_cache().len(), 1);
+
+        let path = std::env::temp_dir().join(format!("pdf_test_doc_open_{}.pdf", std::process::id()));
+        std::fs::write(&path, &dat).unwrap();
+        let d = OwnedDoc::open(&path);
+        std::fs::remove_file(&path).unwrap();
+        let d = d.unwrap();
+        assert_eq!(d.data(), &dat[..]);
+        assert_eq!(d.trailer().unwrap().root(), Some(ObjRef { num: 1, gen: 0 }));
+        assert!(matches!(OwnedDoc::open(&path), Err(OpenError::Io { .. })));
+        // 借用のDocから取り出したものはOwnedDocが生きている間使える
+        let view = d.doc();
+        let catalog = view.catalog().unwrap();
+        drop(view);
+        assert_eq!(catalog.as_dict().unwrap().get("Type").and_then(Object::as_name), Some(&b"Catalog"[..]));
+        assert_eq!(d.doc().data().as_ptr(), d.data().as_ptr());
+
+        let cut = dat.windows(9).rposition(|v| v == b"startxref").unwrap();
+        assert!(matches!(OwnedDoc::from_vec(dat[..cut].to_vec()), Err(PdfError::StartXRefNotFound)));
+        let d = OwnedDoc::repair_vec(dat[..cut].to_vec()).unwrap();
+        assert_eq!(d.repair_report(), d.doc().repair_report());
+        assert_eq!(d.object(ObjRef { num: 4, gen: 0 }).unwrap().as_bytes(), Some(&b"in stream"[..]));
+    }
+
+    #[test]
+    // 目的：オフセットのずれ・xrefやtrailerの欠落から作り直し、その内容が報告されるかを確認する
+    fn test_doc_repair() {
+        let dat = build_pdf(&["<< /Type /Catalog /Pages 2 0 R >>", "<< /Count 0 >>"]);
+        let d = Doc::repair(&dat).unwrap();
+        assert!(d.repair_report().is_none());
+        assert_eq!(d.table().unwrap().sections().len(), 1);
+
+        // オブジェクトの前に余計な行が入り、xrefのオフセットが全部ずれた (startxrefは正しい)
+        let start = crate::pdf::xref::find_startxref(&dat).unwrap();
+        let shifted = String::from_utf8(dat).unwrap()
+            .replacen("1 0 obj", "%junk\n1 0 obj", 1)
+            .replace(&format!("startxref\n{}", start), &format!("startxref\n{}", start + 6));
+        let d = Doc::repair(shifted.as_bytes()).unwrap();
+        let report = d.repair_report().unwrap();
+        assert_eq!(report.reason, RepairReason::BadOffsets(vec![1, 2]));
+        assert_eq!(report.objects, 2);
+        assert!(!report.root_guessed);
+        let pages = d.resolve(d.catalog().unwrap().as_dict().unwrap().get("Pages").unwrap()).unwrap();
+        assert_eq!(pages.as_dict().unwrap().get("Count"), Some(&Object::Integer(0)));
+
+        // startxrefが無い相互参照ストリームのファイル
+        let dat = build_pdf_with_streams();
+        let cut = dat.windows(9).rposition(|v| v == b"startxref").unwrap();
+        let d = Doc::repair(&dat[..cut]).unwrap();
+        let report = d.repair_report().unwrap();
+        assert_eq!(report.reason, RepairReason::Unreadable(PdfError::StartXRefNotFound));
+        assert_eq!((report.objects, report.compressed, report.trailers.len()), (5, 2, 1));
+        let t = d.trailer().unwrap();
+        assert_eq!((t.size(), t.root()), (Some(6), Some(ObjRef { num: 1, gen: 0 })));
+        assert_eq!(d.object(ObjRef { num: 4, gen: 0 }).unwrap().as_bytes(), Some(&b"in stream"[..]));
+
+        // xrefもtrailerも無い。/Rootはカタログから補い、/Sizeは見つけた番号から決める
+        let dat = b"%PDF-1.4\n1 0 obj (a) endobj\n7 0 obj << /Type /Catalog >> endobj\n";
+        let d = Doc::repair(dat).unwrap();
+        assert!(d.repair_report().unwrap().root_guessed);
+        let t = d.trailer().unwrap();
+        assert_eq!((t.size(), t.root()), (Some(8), Some(ObjRef { num: 7, gen: 0 })));
+        assert_eq!(Doc::repair(b"%PDF-1.4\n").err(), Some(PdfError::NoObjectsFound));
+    }
+
+    #[test]
+    // 目的：/Prevが読んだxrefに戻る場合にエラーになるかを確認する
+    fn test_doc_prev_loop() {
+        let dat = build_pdf(&["<< /Type /Catalog >>"]);
+        let start = crate::pdf::xref::find_startxref(&dat).unwrap();
+        let s = String::from_utf8(dat).unwrap().replace(" >>\nstartxref", &format!(" /Prev {} >>\nstartxref", start));
+        assert_eq!(Doc::new(s.as_bytes()).err(), Some(PdfError::PrevLoop { offset: start }));
+
+        let mut dat = build_pdf(&["<< /Type /Catalog >>"]);
+        append_update(&mut dat, &[(1, "<< /Type /Catalog >>")], "");
+        let dat = String::from_utf8(dat).unwrap().replacen("/Prev ", "/Prev -", 1);
+        assert!(matches!(Doc::new(dat.as_bytes()).err(), Some(PdfError::BadTrailer { .. })));
+
+        // /Prevがファイルの外を指す。repairなら走査して作り直せる
+        let dat = build_pdf(&["<< /Type /Catalog >>"]);
+        let s = String::from_utf8(dat).unwrap().replace(" >>\nstartxref", " /Prev 999999 >>\nstartxref");
+        assert_eq!(Doc::new(s.as_bytes()).err(), Some(PdfError::XRefNotFound { offset: 999999 }));
+        let d = Doc::repair(s.as_bytes()).unwrap();
+        assert_eq!(d.repair_report().unwrap().reason, RepairReason::Unreadable(PdfError::XRefNotFound { offset: 999999 }));
+        assert!(OwnedDoc::repair_vec(s.into_bytes()).unwrap().repair_report().is_some());
+
+        // /XRefStmも同じ
+        let mut dat = build_pdf(&["<< /Type /Catalog >>"]);
+        append_update(&mut dat, &[(1, "<< /Type /Catalog >>")], "/XRefStm 999999");
+        assert!(Doc::new(&dat).is_err());
+        assert!(Doc::repair(&dat).unwrap().repair_report().is_some());
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Write};
+
+use super::lexer::is_delimiter;
+use super::object::{ObjRef, Object};
+use super::xref::is_whitespace;
+use super::{Doc, XRefEntryKind};
+
+// Docの中身を字下げした木として書き出す。
+//
+//   trailer         trailerの辞書 (参照はたどらない)
+//   xref            エントリを番号順に
+//   objects         trailerの参照から順に、参照先のオブジェクトをその場で展開する
+//
+// 各オブジェクトは最初に出てきた所で1度だけ展開し、2度目以降は参照のまま印を付ける。
+//   (back-reference)  展開している途中のオブジェクトへの参照 (/Parent など)
+//   (see above)       既に展開し終えたオブジェクトへの参照
+// trailerからたどれなかったオブジェクトは最後に番号順で並べる。
+// ストリームは辞書と元のバイト数だけを書き、中身は展開しない。
+
+// 参照の展開の入れ子の上限。これより深いものは参照のままにし、後で並べる
+const MAX_DEPTH: usize = 32;
+
+pub fn tree(doc: &Doc) -> String {
+    let mut d = Dumper { doc, out: String::new(), seen: BTreeMap::new(), depth: 0 };
+    // Stringへの書き込みは失敗しない
+    let _ = d.write();
+    d.out
+}
+
+struct Dumper<'d, 'a> {
+    doc: &'d Doc<'a>,
+    out: String,
+    // 展開を始めたオブジェクト。値は展開し終えたかどうか
+    seen: BTreeMap<ObjRef, bool>,
+    depth: usize,
+}
+
+impl<'d, 'a> Dumper<'d, 'a> {
+    fn write(&mut self) -> fmt::Result {
+        let Some(table) = self.doc.table() else {
+            return writeln!(self.out, "(no xref)");
+        };
+        writeln!(self.out, "trailer")?;
+        for (k, v) in table.trailer().dict().iter() {
+            self.value(v, 2, &format!("{} ", name(k)), false)?;
+        }
+
+        match self.doc.repair_report() {
+            Some(r) => writeln!(self.out, "xref (repaired: {:?})", r.reason)?,
+            None => writeln!(self.out, "xref (sections at {:?})", table.sections())?,
+        }
+        for e in table.entries() {
+            let kind = match e.kind {
+                XRefEntryKind::Free { next } => format!("free next={}", next),
+                XRefEntryKind::InUse { offset } => format!("offset={}", offset),
+                XRefEntryKind::Compressed { stream, index } => format!("in stream {} index={}", stream, index),
+            };
+            writeln!(self.out, "  {} {} {}", e.obj_num, e.gen, kind)?;
+        }
+
+        writeln!(self.out, "objects")?;
+        let roots: Vec<ObjRef> = table.trailer().dict().iter().filter_map(|(_, v)| v.as_reference()).collect();
+        for r in roots {
+            if !self.seen.contains_key(&r) {
+                self.object(r, 2)?;
+            }
+        }
+        let rest: Vec<ObjRef> = table
+            .entries()
+            .iter()
+            .filter(|e| !matches!(e.kind, XRefEntryKind::Free { .. }))
+            .map(|e| ObjRef { num: e.obj_num, gen: e.gen })
+            .collect();
+        for r in rest {
+            if !self.seen.contains_key(&r) {
+                self.object(r, 2)?;
+            }
+        }
+        Ok(())
+    }
+
+    // "N G obj" に続けて中身
+    fn object(&mut self, r: ObjRef, indent: usize) -> fmt::Result {
+        self.seen.insert(r, false);
+        self.depth += 1;
+        let head = format!("{} {} obj ", r.num, r.gen);
+        match self.doc.object(r) {
+            Ok(o) => self.value(&o, indent, &head, true)?,
+            Err(e) => writeln!(self.out, "{:indent$}{}(error: {})", "", head, e)?,
+        }
+        self.depth -= 1;
+        self.seen.insert(r, true);
+        Ok(())
+    }
+
+    // 1行目をprefixに続けて書く。followなら参照先を展開する
+    fn value(&mut self, obj: &Object, indent: usize, prefix: &str, follow: bool) -> fmt::Result {
+        match obj {
+            Object::Dict(d) => {
+                writeln!(self.out, "{:indent$}{}<<", "", prefix)?;
+                for (k, v) in d.iter() {
+                    self.value(v, indent + 2, &format!("{} ", name(k)), follow)?;
+                }
+                writeln!(self.out, "{:indent$}>>", "")
+            }
+            Object::Array(v) if v.iter().all(|v| self.inline(v, follow)) => {
+                let items: Vec<String> = v.iter().map(|v| self.scalar(v)).collect();
+                writeln!(self.out, "{:indent$}{}[{}]", "", prefix, items.join(" "))
+            }
+            Object::Array(v) => {
+                writeln!(self.out, "{:indent$}{}[", "", prefix)?;
+                for v in v {
+                    self.value(v, indent + 2, "", follow)?;
+                }
+                writeln!(self.out, "{:indent$}]", "")
+            }
+            Object::Stream(s) => {
+                self.value(&Object::Dict(s.dict.clone()), indent, prefix, follow)?;
+                // 中身は展開しない。/Filterは上の辞書に出ている
+                writeln!(self.out, "{:indent$}stream {} bytes", "", s.data.len())
+            }
+            Object::Ref(r) if follow && self.expandable(*r) => {
+                writeln!(self.out, "{:indent$}{}{} {} R =>", "", prefix, r.num, r.gen)?;
+                self.object(*r, indent + 2)
+            }
+            _ => writeln!(self.out, "{:indent$}{}{}", "", prefix, self.scalar(obj)),
+        }
+    }
+
+    // 1行に書けるもの
+    fn inline(&self, obj: &Object, follow: bool) -> bool {
+        match obj {
+            Object::Dict(_) | Object::Array(_) | Object::Stream(_) => false,
+            Object::Ref(r) => !(follow && self.expandable(*r)),
+            _ => true,
+        }
+    }
+
+    fn expandable(&self, r: ObjRef) -> bool {
+        !self.seen.contains_key(&r) && self.depth < MAX_DEPTH
+    }
+
+    fn scalar(&self, obj: &Object) -> String {
+        match obj {
+            Object::Null => "null".to_string(),
+            Object::Bool(v) => v.to_string(),
+            Object::Integer(v) => v.to_string(),
+            Object::Real(v) => v.to_string(),
+            Object::Name(v) => name(v),
+            Object::String(v) => string(v),
+            Object::Ref(r) => match self.seen.get(r) {
+                Some(false) => format!("{} {} R (back-reference)", r.num, r.gen),
+                Some(true) => format!("{} {} R (see above)", r.num, r.gen),
+                None => format!("{} {} R", r.num, r.gen),
+            },
+            // scalarには来ない
+            Object::Dict(_) | Object::Array(_) | Object::Stream(_) => "...".to_string(),
+        }
+    }
+}
+
+// PDFの書き方に戻した名前。区切り文字や空白、表示できない文字は #xx
+fn name(v: &[u8]) -> String {
+    let mut s = String::from("/");
+    for c in v {
+        if c.is_ascii_graphic() && !is_delimiter(*c) && !is_whitespace(*c) && *c != b'#' {
+            s.push(*c as char);
+        } else {
+            s += &format!("#{:02X}", c);
+        }
+    }
+    s
+}
+
+// 表示できる文字だけなら (...)、それ以外は <16進>
+fn string(v: &[u8]) -> String {
+    if v.iter().all(|c| c.is_ascii_graphic() || *c == b' ') {
+        let mut s = String::from("(");
+        for c in v {
+            if matches!(c, b'(' | b')' | b'\\') {
+                s.push('\\');
+            }
+            s.push(*c as char);
+        }
+        s + ")"
+    } else {
+        let hex: String = v.iter().map(|c| format!("{:02X}", c)).collect();
+        format!("<{}>", hex)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf::dump::*;
+    use crate::pdf::tests::build_pdf;
+
+    #[test]
+    // 目的：参照をその場で展開し、循環する参照は印を付けて止まるかを確認する
+    fn test_dump_tree() {
+        let dat = build_pdf(&[
+            "<< /Type /Catalog /Pages 2 0 R /Names [(a b) <00ff>] >>",
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 /Parent 2 0 R >>",
+            "<< /Type /Page /Parent 2 0 R /Self 3 0 R /Root 1 0 R /Contents 4 0 R /My#20Key 1.5 >>",
+            "<< /Length 3 >>\nstream\nabc\nendstream",
+            "(orphan)",
+        ]);
+        let d = Doc::new(&dat).unwrap();
+        let s = tree(&d);
+        let offset = |num| match d.table().unwrap().get(num).unwrap().kind {
+            XRefEntryKind::InUse { offset } => offset,
+            _ => panic!(),
+        };
+        let expected = format!(
+            "trailer
+  /Size 6
+  /Root 1 0 R
+xref (sections at {:?})
+  0 65535 free next=0
+  1 0 offset={}
+  2 0 offset={}
+  3 0 offset={}
+  4 0 offset={}
+  5 0 offset={}
+objects
+  1 0 obj <<
+    /Type /Catalog
+    /Pages 2 0 R =>
+      2 0 obj <<
+        /Type /Pages
+        /Kids [
+          3 0 R =>
+            3 0 obj <<
+              /Type /Page
+              /Parent 2 0 R (back-reference)
+              /Self 3 0 R (back-reference)
+              /Root 1 0 R (back-reference)
+              /Contents 4 0 R =>
+                4 0 obj <<
+                  /Length 3
+                >>
+                stream 3 bytes
+              /My#20Key 1.5
+            >>
+        ]
+        /Count 1
+        /Parent 2 0 R (back-reference)
+      >>
+    /Names [(a b) <00FF>]
+  >>
+  5 0 obj (orphan)
+",
+            d.table().unwrap().sections(),
+            offset(1),
+            offset(2),
+            offset(3),
+            offset(4),
+            offset(5)
+        );
+        assert_eq!(s, expected);
+    }
+}
@@ -0,0 +1,520 @@
+use std::borrow::Cow;
+use thiserror::Error;
+
+pub mod flate;
+pub mod lzw;
+
+use super::PdfError;
+use super::object::{Dict, Object};
+use super::xref::is_whitespace;
+
+// ストリームのフィルタ。
+// /Filter に並んだ順に展開し、/DecodeParms の同じ位置にある辞書をパラメータとする。
+// 画像用のフィルタ (DCTDecode, JPXDecode, JBIG2Decode, CCITTFaxDecode) と暗号化 (Crypt) は扱わない。
+// 小さな入力から巨大な出力を作れるので、展開後の大きさはMAX_DECODED_LENまでとする。
+
+// 1つのフィルタが出力してよいバイト数
+pub const MAX_DECODED_LEN: usize = 256 << 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FilterError {
+    #[error("invalid {filter} data at byte {pos}")]
+    InvalidData { filter: &'static str, pos: usize },
+    #[error("unsupported predictor {0}")]
+    UnsupportedPredictor(i64),
+    #[error("malformed decode parameters")]
+    BadParms,
+    #[error("{filter} output exceeds {limit} bytes")]
+    TooLarge { filter: &'static str, limit: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Flate,
+    Lzw,
+    AsciiHex,
+    Ascii85,
+    RunLength,
+}
+
+// /DecodeParms のうち、ここで使うもの
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeParms {
+    // 1: なし, 2: TIFF, 10〜15: PNG
+    pub predictor: i64,
+    pub colors: usize,
+    pub bits_per_component: usize,
+    pub columns: usize,
+    // LZWDecodeだけが使う
+    pub early_change: bool,
+}
+
+impl Default for DecodeParms {
+    fn default() -> Self {
+        DecodeParms { predictor: 1, colors: 1, bits_per_component: 8, columns: 1, early_change: true }
+    }
+}
+
+impl DecodeParms {
+    // 辞書が無いかnullなら既定値
+    pub fn from_dict(dict: Option<&Dict>) -> Result<Self, FilterError> {
+        let mut parms = DecodeParms::default();
+        let Some(dict) = dict else {
+            return Ok(parms);
+        };
+        let get = |key: &str| match dict.get(key) {
+            None => Ok(None),
+            Some(v) => v.as_int().map(Some).ok_or(FilterError::BadParms),
+        };
+        let size = |v: i64| usize::try_from(v).ok().filter(|v| *v >= 1).ok_or(FilterError::BadParms);
+        if let Some(v) = get("Predictor")? {
+            parms.predictor = v;
+        }
+        if let Some(v) = get("Colors")? {
+            parms.colors = size(v)?;
+        }
+        if let Some(v) = get("BitsPerComponent")? {
+            parms.bits_per_component = size(v)?;
+        }
+        if let Some(v) = get("Columns")? {
+            parms.columns = size(v)?;
+        }
+        if let Some(v) = get("EarlyChange")? {
+            parms.early_change = v != 0;
+        }
+        if ![1, 2, 4, 8, 16].contains(&parms.bits_per_component) {
+            return Err(FilterError::BadParms);
+        }
+        Ok(parms)
+    }
+
+    // 1画素のバイト数 (1未満なら1) と1行のバイト数
+    fn row_size(&self) -> Result<(usize, usize), FilterError> {
+        let bits = self
+            .colors
+            .checked_mul(self.bits_per_component)
+            .and_then(|v| v.checked_mul(self.columns))
+            .ok_or(FilterError::BadParms)?;
+        Ok(((self.colors * self.bits_per_component).div_ceil(8), bits.div_ceil(8)))
+    }
+}
+
+impl Filter {
+    // 正式な名前と、インライン画像で使われる略称
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"FlateDecode" | b"Fl" => Some(Filter::Flate),
+            b"LZWDecode" | b"LZW" => Some(Filter::Lzw),
+            b"ASCIIHexDecode" | b"AHx" => Some(Filter::AsciiHex),
+            b"ASCII85Decode" | b"A85" => Some(Filter::Ascii85),
+            b"RunLengthDecode" | b"RL" => Some(Filter::RunLength),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Filter::Flate => "FlateDecode",
+            Filter::Lzw => "LZWDecode",
+            Filter::AsciiHex => "ASCIIHexDecode",
+            Filter::Ascii85 => "ASCII85Decode",
+            Filter::RunLength => "RunLengthDecode",
+        }
+    }
+
+    pub fn decode(self, dat: &[u8], parms: &DecodeParms) -> Result<Vec<u8>, FilterError> {
+        self.decode_with_limit(dat, parms, MAX_DECODED_LEN)
+    }
+
+    // 出力がlimitバイトを超えればTooLarge。予測子はFlateDecodeとLZWDecodeのあとにだけ掛ける
+    pub fn decode_with_limit(self, dat: &[u8], parms: &DecodeParms, limit: usize) -> Result<Vec<u8>, FilterError> {
+        match self {
+            Filter::Flate => unpredict(flate::inflate(dat, limit)?, parms),
+            Filter::Lzw => unpredict(lzw::decode(dat, parms.early_change, limit)?, parms),
+            // 出力は入力の半分以下
+            Filter::AsciiHex => ascii_hex(dat),
+            Filter::Ascii85 => ascii85(dat, limit),
+            Filter::RunLength => run_length(dat, limit),
+        }
+    }
+}
+
+// ストリーム辞書の /Filter と /DecodeParms から、掛ける順のフィルタを作る。offsetはエラーに付けるストリームの位置
+pub fn chain(dict: &Dict, offset: usize) -> Result<Vec<(Filter, DecodeParms)>, PdfError> {
+    let (names, parms): (Vec<&Object>, Vec<&Object>) = match (dict.get("Filter"), dict.get("DecodeParms")) {
+        (None, _) => return Ok(vec![]),
+        (Some(Object::Array(f)), Some(Object::Array(p))) => (f.iter().collect(), p.iter().collect()),
+        (Some(Object::Array(f)), p) => (f.iter().collect(), p.into_iter().collect()),
+        (Some(f), p) => (vec![f], p.into_iter().collect()),
+    };
+    let mut out = vec![];
+    for (i, name) in names.into_iter().enumerate() {
+        let filter = name.as_name().and_then(Filter::from_name).ok_or_else(|| PdfError::UnsupportedFilter {
+            filter: String::from_utf8_lossy(name.as_name().unwrap_or(b"?")).into_owned(),
+            offset,
+        })?;
+        let p = match parms.get(i) {
+            None | Some(Object::Null) => None,
+            Some(Object::Dict(v)) => Some(v),
+            Some(_) => return Err(failed(filter, offset, FilterError::BadParms)),
+        };
+        let p = DecodeParms::from_dict(p).map_err(|e| failed(filter, offset, e))?;
+        out.push((filter, p));
+    }
+    Ok(out)
+}
+
+// フィルタを順に掛ける。フィルタが無ければ借用のまま
+pub fn decode<'d>(dat: &'d [u8], dict: &Dict, offset: usize) -> Result<Cow<'d, [u8]>, PdfError> {
+    let mut out = Cow::Borrowed(dat);
+    for (filter, parms) in chain(dict, offset)? {
+        out = Cow::Owned(filter.decode(&out, &parms).map_err(|e| failed(filter, offset, e))?);
+    }
+    Ok(out)
+}
+
+fn failed(filter: Filter, offset: usize, source: FilterError) -> PdfError {
+    PdfError::Filter { filter: filter.name().to_string(), offset, source }
+}
+
+// outにさらにnバイト足してもlimitを超えないか
+fn ensure_room(out: &[u8], n: usize, limit: usize, filter: &'static str) -> Result<(), FilterError> {
+    match out.len().checked_add(n) {
+        Some(v) if v <= limit => Ok(()),
+        _ => Err(FilterError::TooLarge { filter, limit }),
+    }
+}
+
+fn ascii_hex(dat: &[u8]) -> Result<Vec<u8>, FilterError> {
+    let mut out = vec![];
+    let mut high = None;
+    for (i, b) in dat.iter().enumerate() {
+        if *b == b'>' {
+            break;
+        }
+        if is_whitespace(*b) {
+            continue;
+        }
+        let v = (*b as char).to_digit(16).ok_or(FilterError::InvalidData { filter: "ASCIIHexDecode", pos: i })? as u8;
+        match high.take() {
+            None => high = Some(v),
+            Some(h) => out.push(h << 4 | v),
+        }
+    }
+    // 桁が奇数なら最後に0を補う
+    if let Some(h) = high {
+        out.push(h << 4);
+    }
+    Ok(out)
+}
+
+fn ascii85(dat: &[u8], limit: usize) -> Result<Vec<u8>, FilterError> {
+    let invalid = |pos| FilterError::InvalidData { filter: "ASCII85Decode", pos };
+    let mut out = vec![];
+    let (mut acc, mut n) = (0u64, 0);
+    let start = if dat.starts_with(b"<~") { 2 } else { 0 };
+    for (i, b) in dat.iter().enumerate().skip(start) {
+        match b {
+            b'~' => break,
+            b'z' if n == 0 => {
+                ensure_room(&out, 4, limit, "ASCII85Decode")?;
+                out.extend_from_slice(&[0; 4]);
+            }
+            b'!'..=b'u' => {
+                acc = acc * 85 + (b - b'!') as u64;
+                n += 1;
+                if n == 5 {
+                    let v = u32::try_from(acc).map_err(|_| invalid(i))?;
+                    ensure_room(&out, 4, limit, "ASCII85Decode")?;
+                    out.extend_from_slice(&v.to_be_bytes());
+                    (acc, n) = (0, 0);
+                }
+            }
+            _ if is_whitespace(*b) => {}
+            _ => return Err(invalid(i)),
+        }
+    }
+    // 最後の半端な組は 'u' で埋めて、文字数-1 バイトだけ取る
+    if n == 1 {
+        return Err(invalid(dat.len()));
+    }
+    if n > 1 {
+        for _ in n..5 {
+            acc = acc * 85 + 84;
+        }
+        let v = u32::try_from(acc).map_err(|_| invalid(dat.len()))?;
+        ensure_room(&out, n - 1, limit, "ASCII85Decode")?;
+        out.extend_from_slice(&v.to_be_bytes()[..n - 1]);
+    }
+    Ok(out)
+}
+
+fn run_length(dat: &[u8], limit: usize) -> Result<Vec<u8>, FilterError> {
+    let invalid = |pos| FilterError::InvalidData { filter: "RunLengthDecode", pos };
+    let mut out = vec![];
+    let mut i = 0;
+    while let Some(len) = dat.get(i) {
+        i += 1;
+        match *len {
+            128 => break,
+            0..=127 => {
+                let n = *len as usize + 1;
+                ensure_room(&out, n, limit, "RunLengthDecode")?;
+                out.extend_from_slice(dat.get(i..i + n).ok_or(invalid(i))?);
+                i += n;
+            }
+            _ => {
+                let b = *dat.get(i).ok_or(invalid(i))?;
+                ensure_room(&out, 257 - *len as usize, limit, "RunLengthDecode")?;
+                out.resize(out.len() + 257 - *len as usize, b);
+                i += 1;
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn unpredict(dat: Vec<u8>, parms: &DecodeParms) -> Result<Vec<u8>, FilterError> {
+    match parms.predictor {
+        1 => Ok(dat),
+        2 => tiff(dat, parms),
+        10..=15 => png(&dat, parms),
+        v => Err(FilterError::UnsupportedPredictor(v)),
+    }
+}
+
+// PNGの予測子。各行の先頭の1バイトがその行の方式 (10〜15のどれが指定されていても行ごとの指定に従う)
+// 出力は入力より短く、行の長さは/Columnsが大きくても入力の長さまでしか確保しない
+fn png(dat: &[u8], parms: &DecodeParms) -> Result<Vec<u8>, FilterError> {
+    let (bpp, len) = parms.row_size()?;
+    let mut out = Vec::with_capacity(dat.len() / (len + 1) * len);
+    let mut prev = vec![0u8; len.min(dat.len())];
+    for (r, row) in dat.chunks(len + 1).enumerate() {
+        let (kind, row) = (row[0], &row[1..]);
+        // 最後の行が欠けていれば、あるところまでを使う
+        let mut cur = row.to_vec();
+        for i in 0..cur.len() {
+            let a = if i >= bpp { cur[i - bpp] } else { 0 };
+            let b = prev[i];
+            let c = if i >= bpp { prev[i - bpp] } else { 0 };
+            let pred = match kind {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(FilterError::InvalidData { filter: "PNG predictor", pos: r * (len + 1) }),
+            };
+            cur[i] = cur[i].wrapping_add(pred);
+        }
+        out.extend_from_slice(&cur);
+        prev[..cur.len()].copy_from_slice(&cur);
+    }
+    Ok(out)
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = ((p - a as i16).abs(), (p - b as i16).abs(), (p - c as i16).abs());
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// TIFFの予測子2。各成分を同じ行の1画素前の同じ成分との差として持つ
+fn tiff(mut dat: Vec<u8>, parms: &DecodeParms) -> Result<Vec<u8>, FilterError> {
+    let (_, len) = parms.row_size()?;
+    let bpc = parms.bits_per_component;
+    let n = parms.colors * parms.columns;
+    let mask = (1u32 << bpc) - 1;
+    for row in dat.chunks_mut(len) {
+        if bpc == 8 {
+            for i in parms.colors..row.len() {
+                row[i] = row[i].wrapping_add(row[i - parms.colors]);
+            }
+            continue;
+        }
+        for i in parms.colors..n {
+            if (i + 1) * bpc > row.len() * 8 {
+                break;
+            }
+            let v = get_bits(row, i * bpc, bpc) + get_bits(row, (i - parms.colors) * bpc, bpc);
+            set_bits(row, i * bpc, bpc, v & mask);
+        }
+    }
+    Ok(dat)
+}
+
+// 上位ビットから数えてpos番目からのnビット
+fn get_bits(row: &[u8], pos: usize, n: usize) -> u32 {
+    (pos..pos + n).fold(0, |v, p| v << 1 | (row[p / 8] >> (7 - p % 8) & 1) as u32)
+}
+
+fn set_bits(row: &mut [u8], pos: usize, n: usize, v: u32) {
+    for (k, p) in (pos..pos + n).enumerate() {
+        let bit = (v >> (n - 1 - k) & 1) as u8;
+        row[p / 8] = row[p / 8] & !(0x80 >> (p % 8)) | bit << (7 - p % 8);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf::filter::*;
+    use crate::pdf::lexer::Lexer;
+    use crate::pdf::object;
+
+    fn dict(src: &str) -> Dict<'_> {
+        object::parse_object(&mut Lexer::new(src.as_bytes(), 0)).unwrap().as_dict().unwrap().clone()
+    }
+
+    fn encode_hex(dat: &[u8]) -> Vec<u8> {
+        let mut s: Vec<u8> = dat.iter().flat_map(|b| format!("{:02X} ", b).into_bytes()).collect();
+        s.push(b'>');
+        s
+    }
+
+    fn encode85(dat: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        for c in dat.chunks(4) {
+            let mut buf = [0u8; 4];
+            buf[..c.len()].copy_from_slice(c);
+            let mut v = u32::from_be_bytes(buf);
+            if c.len() == 4 && v == 0 {
+                out.push(b'z');
+                continue;
+            }
+            let mut digits = [0u8; 5];
+            for d in digits.iter_mut().rev() {
+                *d = (v % 85) as u8 + b'!';
+                v /= 85;
+            }
+            out.extend_from_slice(&digits[..c.len() + 1]);
+            out.push(b'\n');
+        }
+        out.extend_from_slice(b"~>");
+        out
+    }
+
+    // 同じバイトが3つ以上続けば繰り返し、それ以外はそのまま
+    fn encode_rl(dat: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        let mut i = 0;
+        while i < dat.len() {
+            let run = dat[i..].iter().take(128).take_while(|b| **b == dat[i]).count();
+            if run >= 3 {
+                out.extend_from_slice(&[(257 - run) as u8, dat[i]]);
+                i += run;
+            } else {
+                let n = (dat.len() - i).min(128).min(run.max(1));
+                out.push(n as u8 - 1);
+                out.extend_from_slice(&dat[i..i + n]);
+                i += n;
+            }
+        }
+        out.push(128);
+        out
+    }
+
+    // PNGの予測子を行ごとに順に掛ける
+    fn encode_png(dat: &[u8], bpp: usize, len: usize) -> Vec<u8> {
+        let mut out = vec![];
+        let mut prev = vec![0u8; len];
+        for (r, row) in dat.chunks(len).enumerate() {
+            let kind = (r % 5) as u8;
+            out.push(kind);
+            for i in 0..len {
+                let a = if i >= bpp { row[i - bpp] } else { 0 };
+                let b = prev[i];
+                let c = if i >= bpp { prev[i - bpp] } else { 0 };
+                let pred = match kind {
+                    0 => 0,
+                    1 => a,
+                    2 => b,
+                    3 => ((a as u16 + b as u16) / 2) as u8,
+                    _ => paeth(a, b, c),
+                };
+                out.push(row[i].wrapping_sub(pred));
+            }
+            prev.copy_from_slice(row);
+        }
+        out
+    }
+
+    #[test]
+    // 目的：ASCIIHex・ASCII85・RunLengthがそれぞれの符号化の逆になっているかを確認する
+    fn test_filter_ascii_and_run_length() {
+        let dat: Vec<u8> = (0..1000u32).map(|v| if v % 100 < 40 { 0 } else { (v * 31 % 256) as u8 }).collect();
+        let p = DecodeParms::default();
+        for n in [0, 1, 2, 3, 4, 5, 999, 1000] {
+            assert_eq!(Filter::AsciiHex.decode(&encode_hex(&dat[..n]), &p).unwrap(), &dat[..n]);
+            assert_eq!(Filter::Ascii85.decode(&encode85(&dat[..n]), &p).unwrap(), &dat[..n]);
+            assert_eq!(Filter::RunLength.decode(&encode_rl(&dat[..n]), &p).unwrap(), &dat[..n]);
+        }
+        assert_eq!(Filter::AsciiHex.decode(b"61 6\n2 7>", &p).unwrap(), b"abp");
+        assert_eq!(Filter::Ascii85.decode(b"<~9jqo^~>", &p).unwrap(), b"Man ");
+        assert_eq!(
+            Filter::AsciiHex.decode(b"6x", &p).err(),
+            Some(FilterError::InvalidData { filter: "ASCIIHexDecode", pos: 1 })
+        );
+        assert!(Filter::Ascii85.decode(b"s8W-\"~>", &p).is_err());
+        assert!(Filter::RunLength.decode(&[5, 1, 2], &p).is_err());
+    }
+
+    #[test]
+    // 目的：小さな入力から大きな出力を作るデータが、展開後の上限でエラーになるかを確認する
+    fn test_filter_limit() {
+        let p = DecodeParms::default();
+        // 1組で128バイトになる繰り返しを100組
+        let rl: Vec<u8> = [129, 0].repeat(100);
+        assert_eq!(Filter::RunLength.decode_with_limit(&rl, &p, 12800).unwrap().len(), 12800);
+        assert_eq!(
+            Filter::RunLength.decode_with_limit(&rl, &p, 12799).err(),
+            Some(FilterError::TooLarge { filter: "RunLengthDecode", limit: 12799 })
+        );
+        let z85 = b"zzzz~>";
+        assert_eq!(Filter::Ascii85.decode_with_limit(z85, &p, 16).unwrap(), [0; 16]);
+        assert!(matches!(Filter::Ascii85.decode_with_limit(z85, &p, 15), Err(FilterError::TooLarge { .. })));
+        let z = flate::tests::deflate_fixed(&[0; 1000]);
+        assert!(matches!(Filter::Flate.decode_with_limit(&z, &p, 999), Err(FilterError::TooLarge { .. })));
+
+        // /Columnsが巨大でも、確保するのは入力の長さまで
+        let d = dict("<< /Filter /FlateDecode /DecodeParms << /Predictor 12 /Columns 1000000000000 >> >>");
+        assert_eq!(decode(&flate::tests::deflate_fixed(&[2, 1, 2, 3]), &d, 9).unwrap(), &[1, 2, 3][..]);
+    }
+
+    #[test]
+    // 目的：/Filterと/DecodeParmsの組み合わせどおりに順に展開され、予測子が戻されるかを確認する
+    fn test_filter_chain() {
+        // 3色・8ビット・7列の画像を5行
+        let dat: Vec<u8> = (0..105u32).map(|v| (v * v / 7 + v) as u8).collect();
+        let z = flate::tests::deflate_fixed(&encode_png(&dat, 3, 21));
+        let d = dict("<< /Filter [/ASCIIHexDecode /FlateDecode] /DecodeParms [null << /Predictor 12 /Colors 3 /Columns 7 >>] >>");
+        assert_eq!(decode(&encode_hex(&z), &d, 9).unwrap(), dat);
+
+        // TIFFの予測子 (4ビット・2色・3列)。行ごとに先頭の画素は差を取らない
+        let d = dict("<< /Filter /LZWDecode /DecodeParms << /Predictor 2 /Colors 2 /BitsPerComponent 4 /Columns 3 /EarlyChange 0 >> >>");
+        let enc = [0x12, 0x11, 0xff, 0x34, 0x00, 0x11];
+        assert_eq!(decode(&lzw::tests::encode(&enc, false), &d, 9).unwrap(), &[0x12, 0x23, 0x12, 0x34, 0x34, 0x45][..]);
+
+        let d = dict("<< >>");
+        assert!(matches!(decode(b"abc", &d, 9).unwrap(), Cow::Borrowed(b"abc")));
+        let d = dict("<< /Filter /DCTDecode >>");
+        assert_eq!(
+            decode(b"abc", &d, 9).err(),
+            Some(PdfError::UnsupportedFilter { filter: "DCTDecode".to_string(), offset: 9 })
+        );
+        let d = dict("<< /Filter /FlateDecode /DecodeParms << /Predictor 3 >> >>");
+        assert_eq!(
+            decode(&flate::tests::deflate_stored(b"x"), &d, 9).err(),
+            Some(PdfError::Filter {
+                filter: "FlateDecode".to_string(),
+                offset: 9,
+                source: FilterError::UnsupportedPredictor(3)
+            })
+        );
+    }
+}
@@ -0,0 +1,355 @@
+use super::{ensure_room, FilterError};
+
+// FlateDecode (zlib / deflate) の展開。
+//
+//   - 先頭の2バイトがzlibのヘッダとして正しければ読み飛ばし、そうでなければ生のdeflateとみなす
+//   - 末尾のAdler-32は確かめない (壊れていても中身は読めるファイルが多いため)
+//   - ハフマン符号は符号長ごとの個数と記号の並びだけを持ち、1ビットずつたどって復号する
+//   - 出力がlimitバイトを超えたら、そこでTooLargeにする
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097,
+    6145, 8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13,
+];
+// 符号長の符号長が並ぶ順
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+fn invalid(pos: usize) -> FilterError {
+    FilterError::InvalidData { filter: "FlateDecode", pos }
+}
+
+// 下位ビットから読む
+struct BitReader<'a> {
+    dat: &'a [u8],
+    pos: usize,
+    bit: u32,
+}
+
+impl<'a> BitReader<'a> {
+    fn bits(&mut self, n: u32) -> Result<u32, FilterError> {
+        let mut v = 0;
+        for i in 0..n {
+            let byte = *self.dat.get(self.pos).ok_or(invalid(self.pos))?;
+            v |= ((byte >> self.bit) as u32 & 1) << i;
+            self.bit += 1;
+            if self.bit == 8 {
+                self.bit = 0;
+                self.pos += 1;
+            }
+        }
+        Ok(v)
+    }
+
+    // 格納ブロックの前でバイト境界に揃える
+    fn align(&mut self) {
+        if self.bit != 0 {
+            self.bit = 0;
+            self.pos += 1;
+        }
+    }
+}
+
+struct Huffman {
+    // 符号長ごとの個数
+    count: [u16; MAX_BITS + 1],
+    // 符号順の記号
+    symbol: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Self {
+        let mut count = [0u16; MAX_BITS + 1];
+        for l in lengths {
+            count[*l as usize] += 1;
+        }
+        count[0] = 0;
+        let mut offs = [0u16; MAX_BITS + 2];
+        for i in 1..=MAX_BITS {
+            offs[i + 1] = offs[i] + count[i];
+        }
+        let mut symbol = vec![0; lengths.len()];
+        for (s, l) in lengths.iter().enumerate() {
+            if *l != 0 {
+                symbol[offs[*l as usize] as usize] = s as u16;
+                offs[*l as usize] += 1;
+            }
+        }
+        Huffman { count, symbol }
+    }
+
+    fn decode(&self, r: &mut BitReader) -> Result<u16, FilterError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for len in 1..=MAX_BITS {
+            code |= r.bits(1)? as i32;
+            let count = self.count[len] as i32;
+            if code - first < count {
+                return Ok(self.symbol[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid(r.pos))
+    }
+}
+
+pub fn inflate(dat: &[u8], limit: usize) -> Result<Vec<u8>, FilterError> {
+    let start = match dat {
+        [cmf, flg, ..] if cmf & 0x0f == 8 && (*cmf as u16 * 256 + *flg as u16).is_multiple_of(31) => {
+            if flg & 0x20 != 0 {
+                // 事前辞書はPDFでは使われない
+                return Err(invalid(1));
+            }
+            2
+        }
+        _ => 0,
+    };
+    let mut r = BitReader { dat, pos: start, bit: 0 };
+    let mut out = vec![];
+    loop {
+        let last = r.bits(1)? == 1;
+        match r.bits(2)? {
+            0 => stored(&mut r, &mut out, limit)?,
+            1 => {
+                let (lit, dist) = fixed_tables();
+                codes(&mut r, &mut out, &lit, &dist, limit)?;
+            }
+            2 => {
+                let (lit, dist) = dynamic_tables(&mut r)?;
+                codes(&mut r, &mut out, &lit, &dist, limit)?;
+            }
+            _ => return Err(invalid(r.pos)),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+fn stored(r: &mut BitReader, out: &mut Vec<u8>, limit: usize) -> Result<(), FilterError> {
+    r.align();
+    let head = r.dat.get(r.pos..r.pos + 4).ok_or(invalid(r.pos))?;
+    let len = u16::from_le_bytes([head[0], head[1]]);
+    let nlen = u16::from_le_bytes([head[2], head[3]]);
+    if len != !nlen {
+        return Err(invalid(r.pos));
+    }
+    r.pos += 4;
+    let body = r.dat.get(r.pos..r.pos + len as usize).ok_or(invalid(r.pos))?;
+    ensure_room(out, body.len(), limit, "FlateDecode")?;
+    out.extend_from_slice(body);
+    r.pos += len as usize;
+    Ok(())
+}
+
+fn fixed_tables() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (i, l) in lengths.iter_mut().enumerate() {
+        *l = match i {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8,
+        };
+    }
+    (Huffman::new(&lengths), Huffman::new(&[5; 30]))
+}
+
+fn dynamic_tables(r: &mut BitReader) -> Result<(Huffman, Huffman), FilterError> {
+    let nlen = r.bits(5)? as usize + 257;
+    let ndist = r.bits(5)? as usize + 1;
+    let ncode = r.bits(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(invalid(r.pos));
+    }
+    let mut lengths = [0u8; 19];
+    for i in CODE_LENGTH_ORDER.iter().take(ncode) {
+        lengths[*i] = r.bits(3)? as u8;
+    }
+    let lencode = Huffman::new(&lengths);
+
+    let mut lengths = vec![0u8; nlen + ndist];
+    let mut i = 0;
+    while i < nlen + ndist {
+        let sym = lencode.decode(r)?;
+        let (v, repeat) = match sym {
+            0..=15 => (sym as u8, 1),
+            16 => {
+                let prev = *lengths[..i].last().ok_or(invalid(r.pos))?;
+                (prev, 3 + r.bits(2)? as usize)
+            }
+            17 => (0, 3 + r.bits(3)? as usize),
+            _ => (0, 11 + r.bits(7)? as usize),
+        };
+        if i + repeat > nlen + ndist {
+            return Err(invalid(r.pos));
+        }
+        lengths[i..i + repeat].fill(v);
+        i += repeat;
+    }
+    if lengths[256] == 0 {
+        return Err(invalid(r.pos));
+    }
+    Ok((Huffman::new(&lengths[..nlen]), Huffman::new(&lengths[nlen..])))
+}
+
+fn codes(r: &mut BitReader, out: &mut Vec<u8>, lit: &Huffman, dist: &Huffman, limit: usize) -> Result<(), FilterError> {
+    loop {
+        let sym = lit.decode(r)? as usize;
+        match sym {
+            0..=255 => {
+                ensure_room(out, 1, limit, "FlateDecode")?;
+                out.push(sym as u8);
+            }
+            256 => return Ok(()),
+            _ => {
+                let i = sym - 257;
+                if i >= LENGTH_BASE.len() {
+                    return Err(invalid(r.pos));
+                }
+                let len = LENGTH_BASE[i] as usize + r.bits(LENGTH_EXTRA[i] as u32)? as usize;
+                let d = dist.decode(r)? as usize;
+                if d >= DIST_BASE.len() {
+                    return Err(invalid(r.pos));
+                }
+                let back = DIST_BASE[d] as usize + r.bits(DIST_EXTRA[d] as u32)? as usize;
+                if back > out.len() {
+                    return Err(invalid(r.pos));
+                }
+                ensure_room(out, len, limit, "FlateDecode")?;
+                // 重なりがあり得るので1バイトずつ
+                let from = out.len() - back;
+                for k in 0..len {
+                    out.push(out[from + k]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use crate::pdf::filter::flate::*;
+    use crate::pdf::filter::MAX_DECODED_LEN;
+
+    // 上位ビットから並んだハフマン符号を下位ビットから詰める
+    struct BitWriter {
+        out: Vec<u8>,
+        bit: u32,
+    }
+
+    impl BitWriter {
+        fn bits(&mut self, v: u32, n: u32) {
+            for i in 0..n {
+                if self.bit == 0 {
+                    self.out.push(0);
+                }
+                *self.out.last_mut().unwrap() |= (((v >> i) & 1) as u8) << self.bit;
+                self.bit = (self.bit + 1) % 8;
+            }
+        }
+
+        fn code(&mut self, code: u32, len: u32) {
+            for i in (0..len).rev() {
+                self.bits((code >> i) & 1, 1);
+            }
+        }
+    }
+
+    fn fixed_literal(w: &mut BitWriter, v: u32) {
+        match v {
+            0..=143 => w.code(0x30 + v, 8),
+            144..=255 => w.code(0x190 + v - 144, 9),
+            256..=279 => w.code(v - 256, 7),
+            _ => w.code(0xc0 + v - 280, 8),
+        }
+    }
+
+    // 固定ハフマンのブロック1つで圧縮する。直前の同じ3バイトがあれば (距離が小さい場合だけ) 参照にする
+    pub(crate) fn deflate_fixed(dat: &[u8]) -> Vec<u8> {
+        let mut w = BitWriter { out: vec![0x78, 0x01], bit: 0 };
+        w.bits(1, 1);
+        w.bits(1, 2);
+        let mut i = 0;
+        while i < dat.len() {
+            let found = (1..=4.min(i)).find(|back| dat[i..].len() >= 3 && dat[i..i + 3] == dat[i - back..i - back + 3]);
+            match found {
+                Some(back) => {
+                    let mut len = 3;
+                    while len < 10 && i + len < dat.len() && dat[i + len] == dat[i + len - back] {
+                        len += 1;
+                    }
+                    // 長さ3〜10は記号257〜264、距離1〜4は符号0〜3 (拡張ビットなし)
+                    fixed_literal(&mut w, 254 + len as u32);
+                    w.code(back as u32 - 1, 5);
+                    i += len;
+                }
+                None => {
+                    fixed_literal(&mut w, dat[i] as u32);
+                    i += 1;
+                }
+            }
+        }
+        fixed_literal(&mut w, 256);
+        w.out
+    }
+
+    // 格納ブロックだけで圧縮する
+    pub(crate) fn deflate_stored(dat: &[u8]) -> Vec<u8> {
+        let mut out = vec![0x78, 0x01];
+        let chunks: Vec<&[u8]> = if dat.is_empty() { vec![&[]] } else { dat.chunks(0xffff).collect() };
+        for (i, c) in chunks.iter().enumerate() {
+            out.push((i + 1 == chunks.len()) as u8);
+            out.extend_from_slice(&(c.len() as u16).to_le_bytes());
+            out.extend_from_slice(&(!(c.len() as u16)).to_le_bytes());
+            out.extend_from_slice(c);
+        }
+        out
+    }
+
+    #[test]
+    // 目的：格納ブロック・固定ハフマンのブロックが展開できるかを確認する
+    fn test_inflate_stored_and_fixed() {
+        let dat: Vec<u8> = (0..70000u32).map(|v| (v * 7 % 251) as u8).collect();
+        assert_eq!(inflate(&deflate_stored(&dat), MAX_DECODED_LEN).unwrap(), dat);
+        let text = b"abcabcabcabc hello hello aaaaaaaaaaaaaaaaaaaa \xff\x90\x00";
+        assert_eq!(inflate(&deflate_fixed(text), MAX_DECODED_LEN).unwrap(), text);
+        // zlibのヘッダが無い生のdeflate
+        assert_eq!(inflate(&deflate_fixed(text)[2..], MAX_DECODED_LEN).unwrap(), text);
+        assert_eq!(inflate(&deflate_fixed(b""), MAX_DECODED_LEN).unwrap(), b"");
+    }
+
+    #[test]
+    // 目的：動的ハフマンのブロックが展開できるかを確認する
+    fn test_inflate_dynamic() {
+        // zlib.compress(b"".join(b"line %d: the quick brown fox\n" % i for i in range(40)), 9) の出力
+        let z: &[u8] = &[
+            0x78, 0xda, 0x7d, 0xd3, 0xcd, 0x0d, 0x82, 0x00, 0x10, 0x44, 0xe1, 0xbb, 0x55, 0x6c, 0x09, 0xce, 0x8c, 0xe2,
+            0x4f, 0x39, 0x18, 0x0c, 0x44, 0x02, 0xd1, 0x40, 0xa4, 0x7c, 0x63, 0x01, 0xbc, 0xf3, 0x3b, 0xed, 0x97, 0xd9,
+            0x71, 0x98, 0xba, 0x3a, 0xde, 0x6b, 0xe9, 0xbb, 0x7a, 0xaf, 0xc3, 0xe3, 0x55, 0xed, 0x67, 0xfe, 0x4e, 0xf5,
+            0x9c, 0xb7, 0xc3, 0xf8, 0x6f, 0x82, 0x66, 0x68, 0x81, 0x76, 0x82, 0x76, 0x86, 0xd6, 0x40, 0xbb, 0x40, 0xbb,
+            0x42, 0xbb, 0xd1, 0xed, 0x08, 0x43, 0x32, 0x22, 0x1a, 0x91, 0x8d, 0x08, 0x47, 0xa4, 0x23, 0xe2, 0x11, 0xf9,
+            0x88, 0x80, 0x44, 0x42, 0x26, 0x21, 0xe3, 0x76, 0x48, 0xc8, 0x24, 0x64, 0x12, 0x32, 0x09, 0x99, 0x84, 0x4c,
+            0x42, 0x26, 0x21, 0x93, 0x50, 0x48, 0x28, 0x24, 0x14, 0x7c, 0x2f, 0x12, 0x0a, 0x09, 0x85, 0x84, 0x42, 0x42,
+            0x21, 0xa1, 0x90, 0x50, 0x76, 0x84, 0x7e, 0x62, 0xc9, 0x84, 0xf8,
+        ];
+        let text: Vec<u8> = (0..40).flat_map(|i| format!("line {}: the quick brown fox\n", i).into_bytes()).collect();
+        assert_eq!(inflate(z, MAX_DECODED_LEN).unwrap(), text);
+        assert!(inflate(&z[..z.len() / 2], MAX_DECODED_LEN).is_err());
+        // ちょうどlimitまでは展開でき、1バイトでも超えればエラー
+        assert_eq!(inflate(z, text.len()).unwrap(), text);
+        assert_eq!(inflate(z, text.len() - 1), Err(FilterError::TooLarge { filter: "FlateDecode", limit: text.len() - 1 }));
+        assert!(inflate(&deflate_stored(&text), 100).is_err());
+    }
+}
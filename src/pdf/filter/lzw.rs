@@ -0,0 +1,163 @@
+use super::{ensure_room, FilterError};
+
+// LZWDecode の展開。
+//
+//   符号は上位ビットから詰められ、9ビットから始まって最大12ビットまで伸びる
+//   256は表の初期化、257はデータの終わり
+//   /EarlyChange が1 (既定) なら、表がいっぱいになる1つ手前で符号長を伸ばす
+//   出力がlimitバイトを超えたら、そこでTooLargeにする
+
+const CLEAR: usize = 256;
+const EOD: usize = 257;
+
+fn invalid(pos: usize) -> FilterError {
+    FilterError::InvalidData { filter: "LZWDecode", pos }
+}
+
+pub fn decode(dat: &[u8], early_change: bool, limit: usize) -> Result<Vec<u8>, FilterError> {
+    // 各符号は (1つ前の符号, 最後のバイト) で表し、展開時に後ろからたどる
+    let mut table: Vec<(usize, u8)> = (0..=255u8).map(|b| (usize::MAX, b)).collect();
+    table.extend([(usize::MAX, 0), (usize::MAX, 0)]);
+    let mut out = vec![];
+    let mut width = 9;
+    let mut prev: Option<usize> = None;
+    let (mut acc, mut nbits, mut pos) = (0u32, 0u32, 0usize);
+    let mut buf = vec![];
+    loop {
+        while nbits < width {
+            let Some(b) = dat.get(pos) else {
+                // 終わりの符号が無くても、そこまでを結果とする
+                return Ok(out);
+            };
+            acc = (acc << 8) | *b as u32;
+            nbits += 8;
+            pos += 1;
+        }
+        let code = ((acc >> (nbits - width)) & ((1 << width) - 1)) as usize;
+        nbits -= width;
+        match code {
+            CLEAR => {
+                table.truncate(EOD + 1);
+                width = 9;
+                prev = None;
+                continue;
+            }
+            EOD => return Ok(out),
+            _ => {}
+        }
+        let Some(p) = prev else {
+            if code > 255 {
+                return Err(invalid(pos));
+            }
+            ensure_room(&out, 1, limit, "LZWDecode")?;
+            out.push(code as u8);
+            prev = Some(code);
+            continue;
+        };
+        let known = code < table.len();
+        if !known && code != table.len() {
+            return Err(invalid(pos));
+        }
+        // 符号の列を組み立てる。未登録の符号は「前の列 + 前の列の先頭」
+        buf.clear();
+        let mut c = if known { code } else { p };
+        while c != usize::MAX {
+            buf.push(table[c].1);
+            c = table[c].0;
+        }
+        buf.reverse();
+        let head = buf[0];
+        if !known {
+            buf.push(head);
+        }
+        ensure_room(&out, buf.len(), limit, "LZWDecode")?;
+        out.extend_from_slice(&buf);
+        if table.len() < 4096 {
+            table.push((p, head));
+        }
+        let limit = table.len() + early_change as usize;
+        if limit >= 1 << width && width < 12 {
+            width += 1;
+        }
+        prev = Some(code);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::collections::HashMap;
+
+    use crate::pdf::filter::lzw::*;
+    use crate::pdf::filter::MAX_DECODED_LEN;
+
+    // 試験用の素朴な圧縮。表がいっぱいになったら初期化の符号を出す
+    pub(crate) fn encode(dat: &[u8], early_change: bool) -> Vec<u8> {
+        let mut out = vec![];
+        let (mut acc, mut nbits) = (0u64, 0u32);
+        let mut put = |code: usize, width: u32, out: &mut Vec<u8>| {
+            acc = (acc << width) | code as u64;
+            nbits += width;
+            while nbits >= 8 {
+                out.push((acc >> (nbits - 8)) as u8);
+                nbits -= 8;
+            }
+        };
+        let mut dict: HashMap<Vec<u8>, usize> = HashMap::new();
+        let mut next = EOD + 1;
+        let mut width = 9;
+        put(CLEAR, width, &mut out);
+        let mut cur: Vec<u8> = vec![];
+        for b in dat {
+            let mut ext = cur.clone();
+            ext.push(*b);
+            if ext.len() == 1 || dict.contains_key(&ext) {
+                cur = ext;
+                continue;
+            }
+            let code = if cur.len() == 1 { cur[0] as usize } else { dict[&cur] };
+            put(code, width, &mut out);
+            dict.insert(ext, next);
+            next += 1;
+            // 作った符号の次から長くする。展開側は表への追加が1つ遅れるので条件が1つずれる
+            if next + early_change as usize > 1 << width {
+                if width == 12 {
+                    put(CLEAR, width, &mut out);
+                    dict.clear();
+                    next = EOD + 1;
+                    width = 9;
+                } else {
+                    width += 1;
+                }
+            }
+            cur = vec![*b];
+        }
+        if !cur.is_empty() {
+            let code = if cur.len() == 1 { cur[0] as usize } else { dict[&cur] };
+            put(code, width, &mut out);
+            // 展開側は最後の符号で表が1つ増えるので、それに合わせる
+            if next + early_change as usize >= 1 << width && width < 12 {
+                width += 1;
+            }
+        }
+        put(EOD, width, &mut out);
+        put(0, 7, &mut out);
+        out
+    }
+
+    #[test]
+    // 目的：仕様書の例と、表の初期化をまたぐ長い入力が展開できるかを確認する
+    fn test_lzw_decode() {
+        // PDF 32000-1 7.4.4.2 の例
+        let ex = [0x80, 0x0b, 0x60, 0x50, 0x22, 0x0c, 0x0c, 0x85, 0x01];
+        assert_eq!(decode(&ex, true, MAX_DECODED_LEN).unwrap(), [45, 45, 45, 45, 45, 65, 45, 45, 45, 66]);
+
+        let dat: Vec<u8> = (0..20000u32).map(|v| (v * v % 97 + v / 300) as u8).collect();
+        for early in [true, false] {
+            assert_eq!(decode(&encode(&dat, early), early, MAX_DECODED_LEN).unwrap(), dat);
+        }
+        assert_eq!(decode(&encode(b"TOBEORNOTTOBEORTOBEORNOT", true), true, MAX_DECODED_LEN).unwrap(), b"TOBEORNOTTOBEORTOBEORNOT");
+        assert!(decode(&[0xff, 0xff], true, MAX_DECODED_LEN).is_err());
+        assert_eq!(decode(&encode(&dat, true), true, dat.len()).unwrap(), dat);
+        assert_eq!(decode(&encode(&dat, true), true, 1000), Err(FilterError::TooLarge { filter: "LZWDecode", limit: 1000 }));
+    }
+}
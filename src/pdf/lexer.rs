@@ -0,0 +1,187 @@
+use super::PdfError;
+use super::xref::{is_whitespace, skip_whitespace};
+
+// PDFの字句解析。トークンは元のバイト列を指すsliceで、エスケープの解釈はしない。
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token<'a> {
+    Integer(i64),
+    Real(f64),
+    // 先頭の '/' を除いたもの
+    Name(&'a [u8]),
+    // 括弧の内側
+    LiteralString(&'a [u8]),
+    // <> の内側
+    HexString(&'a [u8]),
+    ArrayStart,
+    ArrayEnd,
+    DictStart,
+    DictEnd,
+    // true false null R obj endobj stream endstream trailer など
+    Keyword(&'a [u8]),
+}
+
+pub fn is_delimiter(c: u8) -> bool {
+    matches!(c, b'(' | b')' | b'<' | b'>' | b'[' | b']' | b'{' | b'}' | b'/' | b'%')
+}
+
+fn is_regular(c: u8) -> bool {
+    !is_whitespace(c) && !is_delimiter(c)
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Lexer<'a> {
+    dat: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(dat: &'a [u8], pos: usize) -> Self {
+        Lexer { dat, pos }
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.dat
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn set_pos(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    // 空白とコメントを飛ばす
+    pub fn skip_ws(&mut self) {
+        loop {
+            self.pos = skip_whitespace(self.dat, self.pos);
+            if self.dat.get(self.pos) != Some(&b'%') {
+                break;
+            }
+            while self.pos < self.dat.len() && !matches!(self.dat[self.pos], b'\r' | b'\n') {
+                self.pos += 1;
+            }
+        }
+    }
+
+    pub fn peek(&self) -> Result<Option<(usize, Token<'a>)>, PdfError> {
+        let mut lex = *self;
+        lex.next_token()
+    }
+
+    // (トークンの開始位置, トークン)。末尾ならNone
+    pub fn next_token(&mut self) -> Result<Option<(usize, Token<'a>)>, PdfError> {
+        self.skip_ws();
+        let start = self.pos;
+        let Some(&c) = self.dat.get(start) else {
+            return Ok(None);
+        };
+        let token = match c {
+            b'[' => {
+                self.pos += 1;
+                Token::ArrayStart
+            }
+            b']' => {
+                self.pos += 1;
+                Token::ArrayEnd
+            }
+            b'<' if self.dat.get(start + 1) == Some(&b'<') => {
+                self.pos += 2;
+                Token::DictStart
+            }
+            b'>' if self.dat.get(start + 1) == Some(&b'>') => {
+                self.pos += 2;
+                Token::DictEnd
+            }
+            b'<' => {
+                let len = self.dat[start + 1..].iter()
+                    .position(|c| *c == b'>')
+                    .ok_or(PdfError::UnexpectedEof { offset: start })?;
+                self.pos = start + len + 2;
+                Token::HexString(&self.dat[start + 1..start + 1 + len])
+            }
+            b'(' => {
+                let end = self.literal_string_end(start)?;
+                self.pos = end + 1;
+                Token::LiteralString(&self.dat[start + 1..end])
+            }
+            b'/' => {
+                self.pos = start + 1;
+                while self.pos < self.dat.len() && is_regular(self.dat[self.pos]) {
+                    self.pos += 1;
+                }
+                Token::Name(&self.dat[start + 1..self.pos])
+            }
+            c if is_regular(c) => {
+                while self.pos < self.dat.len() && is_regular(self.dat[self.pos]) {
+                    self.pos += 1;
+                }
+                let word = &self.dat[start..self.pos];
+                match parse_number(word) {
+                    Some(v) => v,
+                    None => Token::Keyword(word),
+                }
+            }
+            _ => return Err(PdfError::UnexpectedToken { offset: start }),
+        };
+        Ok(Some((start, token)))
+    }
+
+    // 括弧の対応を数え、閉じ括弧の位置を返す。\ の次の文字は数えない
+    fn literal_string_end(&self, start: usize) -> Result<usize, PdfError> {
+        let mut depth = 0;
+        let mut i = start;
+        while i < self.dat.len() {
+            match self.dat[i] {
+                b'\\' => i += 1,
+                b'(' => depth += 1,
+                b')' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(i);
+                    }
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+        Err(PdfError::UnexpectedEof { offset: start })
+    }
+}
+
+fn parse_number(word: &[u8]) -> Option<Token<'static>> {
+    let s = std::str::from_utf8(word).ok()?;
+    if !s.bytes().all(|c| c.is_ascii_digit() || matches!(c, b'+' | b'-' | b'.')) {
+        return None;
+    }
+    if let Ok(v) = s.parse::<i64>() {
+        return Some(Token::Integer(v));
+    }
+    // "-.5" や "4." も実数
+    s.parse::<f64>().ok().map(Token::Real)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf::lexer::*;
+
+    #[test]
+    // 目的：各種トークンに分けられ、コメントが飛ばされるかを確認する
+    fn test_lexer() {
+        let dat = b"<< /Size 3 % comment\n/Root 1 0 R /ID [<0a1B> (a(b)\\)c)] /Ratio -.5 >> trailer";
+        let mut lex = Lexer::new(dat, 0);
+        let mut tokens = vec![];
+        while let Some((_, t)) = lex.next_token().unwrap() {
+            tokens.push(t);
+        }
+        assert_eq!(tokens, vec![
+            Token::DictStart, Token::Name(b"Size"), Token::Integer(3),
+            Token::Name(b"Root"), Token::Integer(1), Token::Integer(0), Token::Keyword(b"R"),
+            Token::Name(b"ID"), Token::ArrayStart, Token::HexString(b"0a1B"), Token::LiteralString(b"a(b)\\)c"), Token::ArrayEnd,
+            Token::Name(b"Ratio"), Token::Real(-0.5), Token::DictEnd, Token::Keyword(b"trailer"),
+        ]);
+        assert_eq!(Lexer::new(b"(abc", 0).next_token(), Err(PdfError::UnexpectedEof { offset: 0 }));
+        assert_eq!(Lexer::new(b"  )", 0).next_token(), Err(PdfError::UnexpectedToken { offset: 2 }));
+    }
+}
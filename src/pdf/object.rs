@@ -0,0 +1,490 @@
+use std::borrow::Cow;
+
+use super::PdfError;
+use super::filter;
+use super::lexer::{Lexer, Token};
+
+// PDFのオブジェクト。名前や文字列は元のバイト列から借用し、変換が必要な場合だけ複製する。
+//   - 名前の #xx、リテラル文字列の \ エスケープや改行を含む場合は解釈した結果を持つ
+//   - ストリームの中身は元のバイト列のsliceのまま (フィルタは掛けない)。decodedでフィルタを掛けたものが得られる
+
+// 配列・辞書の入れ子の上限。壊れたファイルで再帰が深くなりすぎないように
+const MAX_DEPTH: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjRef {
+    pub num: u32,
+    pub gen: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Object<'a> {
+    Null,
+    Bool(bool),
+    Integer(i64),
+    Real(f64),
+    // 先頭の '/' を除いたもの
+    Name(Cow<'a, [u8]>),
+    String(Cow<'a, [u8]>),
+    Array(Vec<Object<'a>>),
+    Dict(Dict<'a>),
+    Ref(ObjRef),
+    Stream(Stream<'a>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stream<'a> {
+    pub dict: Dict<'a>,
+    // "stream" の後の改行から "endstream" の前まで
+    pub data: Cow<'a, [u8]>,
+    // dataの元のバイト列での位置
+    pub offset: usize,
+}
+
+// "n g obj ... endobj"
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndirectObject<'a> {
+    pub id: ObjRef,
+    pub obj: Object<'a>,
+}
+
+impl<'a> Object<'a> {
+    pub fn as_int(&self) -> Option<i64> {
+        match self {
+            Object::Integer(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_name(&self) -> Option<&[u8]> {
+        match self {
+            Object::Name(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Object::String(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Object<'a>]> {
+        match self {
+            Object::Array(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&Dict<'a>> {
+        match self {
+            Object::Dict(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    pub fn as_reference(&self) -> Option<ObjRef> {
+        match self {
+            Object::Ref(v) => Some(*v),
+            _ => None,
+        }
+    }
+
+    pub fn as_stream(&self) -> Option<&Stream<'a>> {
+        match self {
+            Object::Stream(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    // 借用をやめて複製したもの
+    pub fn into_owned(self) -> Object<'static> {
+        match self {
+            Object::Null => Object::Null,
+            Object::Bool(v) => Object::Bool(v),
+            Object::Integer(v) => Object::Integer(v),
+            Object::Real(v) => Object::Real(v),
+            Object::Name(v) => Object::Name(Cow::Owned(v.into_owned())),
+            Object::String(v) => Object::String(Cow::Owned(v.into_owned())),
+            Object::Array(v) => Object::Array(v.into_iter().map(Object::into_owned).collect()),
+            Object::Dict(v) => Object::Dict(v.into_owned()),
+            Object::Ref(v) => Object::Ref(v),
+            Object::Stream(v) => Object::Stream(Stream {
+                dict: v.dict.into_owned(),
+                data: Cow::Owned(v.data.into_owned()),
+                offset: v.offset,
+            }),
+        }
+    }
+}
+
+impl<'a> Stream<'a> {
+    // /Filterを掛けた中身。フィルタが無ければ借用のまま
+    pub fn decoded(&self) -> Result<Cow<'_, [u8]>, PdfError> {
+        filter::decode(&self.data, &self.dict, self.offset)
+    }
+
+    // decodedと同じだが、フィルタが無ければ元のバイト列の借用のまま返す
+    pub fn into_decoded(self) -> Result<Cow<'a, [u8]>, PdfError> {
+        let decoded = match filter::decode(&self.data, &self.dict, self.offset)? {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(v) => Some(v),
+        };
+        Ok(decoded.map_or(self.data, Cow::Owned))
+    }
+}
+
+// 出てきた順を保つ辞書
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Dict<'a> {
+    entries: Vec<(Cow<'a, [u8]>, Object<'a>)>,
+}
+
+impl<'a> Dict<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Object<'a>> {
+        self.entries.iter().find(|(k, _)| **k == *key.as_bytes()).map(|(_, v)| v)
+    }
+
+    // 同じキーがあれば置き換える
+    pub fn insert(&mut self, key: Cow<'a, [u8]>, value: Object<'a>) {
+        match self.entries.iter_mut().find(|(k, _)| *k == key) {
+            Some(v) => v.1 = value,
+            None => self.entries.push((key, value)),
+        }
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Object<'a>> {
+        let i = self.entries.iter().position(|(k, _)| **k == *key.as_bytes())?;
+        Some(self.entries.remove(i).1)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &Object<'a>)> {
+        self.entries.iter().map(|(k, v)| (&**k, v))
+    }
+
+    // キーと値を取り出す
+    pub fn entries(self) -> impl Iterator<Item = (Cow<'a, [u8]>, Object<'a>)> {
+        self.entries.into_iter()
+    }
+
+    pub fn into_owned(self) -> Dict<'static> {
+        Dict {
+            entries: self.entries.into_iter()
+                .map(|(k, v)| (Cow::Owned(k.into_owned()), v.into_owned()))
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+// 字句解析器の位置からオブジェクトを1つ読む
+pub fn parse_object<'a>(lex: &mut Lexer<'a>) -> Result<Object<'a>, PdfError> {
+    parse_nested(lex, 0)
+}
+
+fn parse_nested<'a>(lex: &mut Lexer<'a>, depth: usize) -> Result<Object<'a>, PdfError> {
+    let (offset, token) = lex.next_token()?.ok_or(PdfError::UnexpectedEof { offset: lex.pos() })?;
+    if depth > MAX_DEPTH {
+        return Err(PdfError::UnexpectedToken { offset });
+    }
+    let obj = match token {
+        Token::Integer(num) => {
+            // "num gen R" なら間接参照
+            let mut ahead = *lex;
+            match (ahead.next_token()?, ahead.next_token()?) {
+                (Some((_, Token::Integer(gen))), Some((_, Token::Keyword(b"R")))) => {
+                    let r = u32::try_from(num).ok().zip(u16::try_from(gen).ok())
+                        .ok_or(PdfError::UnexpectedToken { offset })?;
+                    *lex = ahead;
+                    Object::Ref(ObjRef { num: r.0, gen: r.1 })
+                }
+                _ => Object::Integer(num),
+            }
+        }
+        Token::Real(v) => Object::Real(v),
+        Token::Name(v) => Object::Name(decode_name(v)),
+        Token::LiteralString(v) => Object::String(decode_literal(v)),
+        Token::HexString(v) => Object::String(Cow::Owned(decode_hex(v, offset)?)),
+        Token::ArrayStart => {
+            let mut v = vec![];
+            loop {
+                match lex.peek()? {
+                    Some((_, Token::ArrayEnd)) => {
+                        lex.next_token()?;
+                        break;
+                    }
+                    Some(_) => v.push(parse_nested(lex, depth + 1)?),
+                    None => return Err(PdfError::UnexpectedEof { offset }),
+                }
+            }
+            Object::Array(v)
+        }
+        Token::DictStart => {
+            let mut dict = Dict::new();
+            loop {
+                match lex.next_token()? {
+                    Some((_, Token::DictEnd)) => break,
+                    Some((_, Token::Name(k))) => {
+                        let v = parse_nested(lex, depth + 1)?;
+                        dict.insert(decode_name(k), v);
+                    }
+                    Some((at, _)) => return Err(PdfError::UnexpectedToken { offset: at }),
+                    None => return Err(PdfError::UnexpectedEof { offset }),
+                }
+            }
+            Object::Dict(dict)
+        }
+        Token::Keyword(b"null") => Object::Null,
+        Token::Keyword(b"true") => Object::Bool(true),
+        Token::Keyword(b"false") => Object::Bool(false),
+        _ => return Err(PdfError::UnexpectedToken { offset }),
+    };
+    Ok(obj)
+}
+
+// "n g obj" から始まる間接オブジェクトを読む。
+// ストリームの /Length が間接参照の場合は length で解決する (解決できなければ "endstream" を探す)
+pub fn parse_indirect<'a>(
+    dat: &'a [u8],
+    offset: usize,
+    length: &dyn Fn(ObjRef) -> Option<i64>,
+) -> Result<IndirectObject<'a>, PdfError> {
+    let mut lex = Lexer::new(dat, offset);
+    let header = (lex.next_token()?, lex.next_token()?, lex.next_token()?);
+    let (Some((at, Token::Integer(num))), Some((_, Token::Integer(gen))), Some((_, Token::Keyword(b"obj")))) = header else {
+        return Err(PdfError::BadObjectHeader { offset });
+    };
+    let id = u32::try_from(num).ok().zip(u16::try_from(gen).ok())
+        .map(|(num, gen)| ObjRef { num, gen })
+        .ok_or(PdfError::BadObjectHeader { offset: at })?;
+    let obj = parse_object(&mut lex)?;
+
+    let obj = match (obj, lex.peek()?) {
+        (Object::Dict(dict), Some((_, Token::Keyword(b"stream")))) => {
+            lex.next_token()?;
+            Object::Stream(parse_stream_body(&mut lex, dict, length)?)
+        }
+        (obj, _) => obj,
+    };
+    // endobj が抜けているファイルもあるので、無くてもエラーにしない
+    if let Some((_, Token::Keyword(b"endobj"))) = lex.peek()? {
+        lex.next_token()?;
+    }
+    Ok(IndirectObject { id, obj })
+}
+
+// "stream" キーワードの直後から
+fn parse_stream_body<'a>(
+    lex: &mut Lexer<'a>,
+    dict: Dict<'a>,
+    length: &dyn Fn(ObjRef) -> Option<i64>,
+) -> Result<Stream<'a>, PdfError> {
+    let dat = lex.data();
+    let mut start = lex.pos();
+    // "stream" の後は CRLF か LF
+    if dat[start..].starts_with(b"\r\n") {
+        start += 2;
+    } else if dat[start..].starts_with(b"\n") || dat[start..].starts_with(b"\r") {
+        start += 1;
+    }
+    let len = match dict.get("Length") {
+        Some(Object::Integer(v)) => Some(*v),
+        Some(Object::Ref(r)) => length(*r),
+        _ => None,
+    };
+
+    // /Length の位置に endstream があればそれを使う
+    let by_length = len.and_then(|v| usize::try_from(v).ok())
+        .and_then(|v| start.checked_add(v))
+        .filter(|end| *end <= dat.len())
+        .filter(|end| {
+            let mut l = Lexer::new(dat, *end);
+            matches!(l.next_token(), Ok(Some((_, Token::Keyword(b"endstream")))))
+        });
+    let end = match by_length {
+        Some(v) => v,
+        None => {
+            let at = dat[start..].windows(9)
+                .position(|v| v == b"endstream")
+                .map(|v| start + v)
+                .ok_or(PdfError::UnexpectedEof { offset: start })?;
+            // endstream の前の改行は中身に含めない
+            let mut end = at;
+            if dat[start..end].ends_with(b"\r\n") {
+                end -= 2;
+            } else if dat[start..end].ends_with(b"\n") || dat[start..end].ends_with(b"\r") {
+                end -= 1;
+            }
+            end
+        }
+    };
+    lex.set_pos(end);
+    match lex.next_token()? {
+        Some((_, Token::Keyword(b"endstream"))) => {}
+        _ => return Err(PdfError::UnexpectedToken { offset: end }),
+    }
+    Ok(Stream { dict, data: Cow::Borrowed(&dat[start..end]), offset: start })
+}
+
+// 名前の #xx を解釈する。無ければ借用のまま
+fn decode_name(v: &[u8]) -> Cow<'_, [u8]> {
+    if !v.contains(&b'#') {
+        return Cow::Borrowed(v);
+    }
+    let mut out = Vec::with_capacity(v.len());
+    let mut i = 0;
+    while i < v.len() {
+        let hex = v.get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (v[i], hex) {
+            (b'#', Some(c)) => {
+                out.push(c);
+                i += 3;
+            }
+            (c, _) => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    Cow::Owned(out)
+}
+
+// リテラル文字列の \ エスケープと改行を解釈する。どちらも無ければ借用のまま
+fn decode_literal(v: &[u8]) -> Cow<'_, [u8]> {
+    if !v.contains(&b'\\') && !v.contains(&b'\r') {
+        return Cow::Borrowed(v);
+    }
+    let mut out = Vec::with_capacity(v.len());
+    let mut i = 0;
+    while i < v.len() {
+        match v[i] {
+            b'\\' => {
+                i += 1;
+                let Some(&c) = v.get(i) else { break };
+                match c {
+                    b'n' => out.push(b'\n'),
+                    b'r' => out.push(b'\r'),
+                    b't' => out.push(b'\t'),
+                    b'b' => out.push(0x08),
+                    b'f' => out.push(0x0C),
+                    b'0'..=b'7' => {
+                        // 最大3桁の8進数
+                        let digits = v[i..].iter().take(3).take_while(|c| matches!(c, b'0'..=b'7')).count();
+                        let code = v[i..i + digits].iter().fold(0u32, |acc, d| acc * 8 + (d - b'0') as u32);
+                        out.push(code as u8);
+                        i += digits - 1;
+                    }
+                    // 行末の \ は改行ごと無視する
+                    b'\r' => {
+                        if v.get(i + 1) == Some(&b'\n') {
+                            i += 1;
+                        }
+                    }
+                    b'\n' => {}
+                    c => out.push(c),
+                }
+                i += 1;
+            }
+            // 改行はすべてLFにする
+            b'\r' => {
+                out.push(b'\n');
+                i += if v.get(i + 1) == Some(&b'\n') { 2 } else { 1 };
+            }
+            c => {
+                out.push(c);
+                i += 1;
+            }
+        }
+    }
+    Cow::Owned(out)
+}
+
+// <> の内側。空白は無視し、桁数が奇数なら最後に0を補う
+fn decode_hex(v: &[u8], offset: usize) -> Result<Vec<u8>, PdfError> {
+    let digits: Vec<u8> = v.iter()
+        .filter(|c| !c.is_ascii_whitespace())
+        .map(|c| (*c as char).to_digit(16).map(|d| d as u8).ok_or(PdfError::UnexpectedToken { offset }))
+        .collect::<Result<_, _>>()?;
+    Ok(digits.chunks(2).map(|v| v[0] << 4 | v.get(1).copied().unwrap_or(0)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf::object::*;
+
+    fn parse(s: &[u8]) -> Result<Object<'_>, PdfError> {
+        parse_object(&mut Lexer::new(s, 0))
+    }
+
+    #[test]
+    // 目的：辞書・配列・参照などが読めるかを確認する
+    fn test_parse_object() {
+        let obj = parse(b"<< /Size 3 /Root 1 0 R /ID [<0a1B> <ff1>] /Kids [1 2 null true] >>").unwrap();
+        let dict = obj.as_dict().unwrap();
+        assert_eq!(dict.get("Size").and_then(Object::as_int), Some(3));
+        assert_eq!(dict.get("Root").and_then(Object::as_reference), Some(ObjRef { num: 1, gen: 0 }));
+        let id = dict.get("ID").and_then(Object::as_array).unwrap();
+        assert_eq!(id[0].as_bytes(), Some(&b"\x0a\x1b"[..]));
+        assert_eq!(id[1].as_bytes(), Some(&b"\xff\x10"[..]));
+        assert_eq!(dict.get("Kids"), Some(&Object::Array(vec![
+            Object::Integer(1), Object::Integer(2), Object::Null, Object::Bool(true),
+        ])));
+        assert_eq!(dict.len(), 4);
+    }
+
+    #[test]
+    // 目的：閉じていない辞書やキーが名前でない辞書がエラーになるかを確認する
+    fn test_parse_object_error() {
+        assert_eq!(parse(b"<< /Size 3"), Err(PdfError::UnexpectedEof { offset: 0 }));
+        assert_eq!(parse(b"<< 3 4 >>"), Err(PdfError::UnexpectedToken { offset: 3 }));
+        assert_eq!(parse(b"<zz>"), Err(PdfError::UnexpectedToken { offset: 0 }));
+        let deep = "[".repeat(200);
+        assert!(parse(deep.as_bytes()).is_err());
+    }
+
+    #[test]
+    // 目的：名前・文字列のエスケープが解釈され、無い場合は借用のままかを確認する
+    fn test_parse_object_escapes() {
+        let obj = parse(b"[/A#20B /Plain (a\\(b\\)\\n\\101\\0531\\\r\nc\rd) (plain)]").unwrap();
+        let v = obj.as_array().unwrap();
+        assert_eq!(v[0], Object::Name(Cow::Owned(b"A B".to_vec())));
+        assert!(matches!(&v[1], Object::Name(Cow::Borrowed(b"Plain"))));
+        assert_eq!(v[2].as_bytes(), Some(&b"a(b)\nA+1c\nd"[..]));
+        assert!(matches!(&v[3], Object::String(Cow::Borrowed(b"plain"))));
+    }
+
+    #[test]
+    // 目的：間接オブジェクトとストリームが読め、/Lengthが違っていてもendstreamで区切られるかを確認する
+    fn test_parse_indirect() {
+        let dat = b"12 0 obj\n<< /Length 5 >>\nstream\r\nhello\r\nendstream\nendobj\n";
+        let o = parse_indirect(dat, 0, &|_| None).unwrap();
+        assert_eq!(o.id, ObjRef { num: 12, gen: 0 });
+        let s = o.obj.as_stream().unwrap();
+        assert_eq!((&*s.data, s.offset), (&b"hello"[..], 33));
+
+        // /Lengthが間接参照
+        let dat = b"3 0 obj << /Length 9 0 R >> stream\nhello world\nendstream endobj";
+        let o = parse_indirect(dat, 0, &|r| (r.num == 9).then_some(11)).unwrap();
+        assert_eq!(&*o.obj.as_stream().unwrap().data, b"hello world");
+        // /Lengthが誤っている
+        let dat = b"3 0 obj << /Length 3 >> stream\nhello world\nendstream endobj";
+        let o = parse_indirect(dat, 0, &|_| None).unwrap();
+        assert_eq!(&*o.obj.as_stream().unwrap().data, b"hello world");
+
+        assert_eq!(parse_indirect(b"3 0 R", 0, &|_| None), Err(PdfError::BadObjectHeader { offset: 0 }));
+    }
+}
@@ -0,0 +1,128 @@
+use std::borrow::Cow;
+use std::fmt;
+use std::ops::Range;
+
+use super::PdfError;
+use super::lexer::{Lexer, Token};
+use super::object::{self, IndirectObject, ObjRef, Object, Stream};
+
+// オブジェクトストリーム (/Type /ObjStm) の中のオブジェクトを取り出す。
+//
+//   中身の先頭に "番号 オフセット" の組が /N 個並び、各オブジェクトは /First + オフセット から始まる
+//   中のオブジェクトの世代は常に0で、ストリームを含むことはない
+// フィルタが無ければ元のバイト列から借用したまま、あれば展開したものから複製して返す。
+// 展開と組の読み取りはdecodeで1度だけ行い、各オブジェクトはDecoded::getで取り出す。
+// 中身の位置は展開後のものなので、エラーにはストリームの中身の位置を付ける。
+
+// 展開して "番号 オフセット" の組を読んだオブジェクトストリーム。Docはストリームの番号ごとにこれを覚えておく
+pub struct Decoded {
+    // フィルタが掛かっていれば展開したもの。無ければNoneで、元のバイト列のspanの部分をそのまま使う
+    data: Option<Vec<u8>>,
+    span: Range<usize>,
+    first: usize,
+    // index順の (番号, /Firstからのオフセット)。読めない組があれば、そこまで
+    pairs: Vec<(u32, usize)>,
+}
+
+impl fmt::Debug for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Decoded")
+            .field("decoded", &self.data.as_ref().map(Vec::len))
+            .field("span", &self.span)
+            .field("pairs", &self.pairs.len())
+            .finish()
+    }
+}
+
+pub fn decode(stm: &Stream) -> Result<Decoded, PdfError> {
+    let (n, first) = header(stm)?;
+    let dat = stm.decoded()?;
+    let mut lex = Lexer::new(&dat, 0);
+    let mut pairs = vec![];
+    for _ in 0..n {
+        match (lex.next_token(), lex.next_token()) {
+            (Ok(Some((_, Token::Integer(num)))), Ok(Some((_, Token::Integer(offset))))) => {
+                match (u32::try_from(num), usize::try_from(offset)) {
+                    (Ok(num), Ok(offset)) => pairs.push((num, offset)),
+                    _ => break,
+                }
+            }
+            _ => break,
+        }
+    }
+    let data = match dat {
+        Cow::Borrowed(_) => None,
+        Cow::Owned(v) => Some(v),
+    };
+    Ok(Decoded { data, span: stm.offset..stm.offset + stm.data.len(), first, pairs })
+}
+
+impl Decoded {
+    // index番目のオブジェクト。datはストリームを含む元のバイト列
+    pub fn get<'d>(&self, dat: &'d [u8], index: u32) -> Result<IndirectObject<'d>, PdfError> {
+        let bad = PdfError::BadObjectStream { offset: self.span.start };
+        let &(num, offset) = self.pairs.get(index as usize).ok_or(bad.clone())?;
+        let id = ObjRef { num, gen: 0 };
+        match &self.data {
+            None => parse_at(dat.get(self.span.clone()).ok_or(bad.clone())?, self.first, offset)
+                .map(|obj| IndirectObject { id, obj })
+                .ok_or(bad),
+            Some(v) => parse_at(v, self.first, offset)
+                .map(|obj| IndirectObject { id, obj: obj.into_owned() })
+                .ok_or(bad),
+        }
+    }
+
+    // ストリームの中身の位置
+    pub fn offset(&self) -> usize {
+        self.span.start
+    }
+
+    // 入っているオブジェクトの番号。index順
+    pub fn numbers(&self) -> impl Iterator<Item = u32> + '_ {
+        self.pairs.iter().map(|v| v.0)
+    }
+}
+
+// 入っているオブジェクトの番号。index順
+pub fn numbers(stm: &Stream) -> Result<Vec<u32>, PdfError> {
+    Ok(decode(stm)?.numbers().collect())
+}
+
+// (/N, /First)
+fn header(stm: &Stream) -> Result<(usize, usize), PdfError> {
+    if stm.dict.get("Type").and_then(Object::as_name) != Some(b"ObjStm") {
+        return Err(PdfError::BadObjectStream { offset: stm.offset });
+    }
+    let n = stm.dict.get("N").and_then(Object::as_int).and_then(|v| usize::try_from(v).ok());
+    let first = stm.dict.get("First").and_then(Object::as_int).and_then(|v| usize::try_from(v).ok());
+    n.zip(first).ok_or(PdfError::BadObjectStream { offset: stm.offset })
+}
+
+fn parse_at(dat: &[u8], first: usize, offset: usize) -> Option<Object<'_>> {
+    let at = first.checked_add(offset).filter(|v| *v < dat.len())?;
+    object::parse_object(&mut Lexer::new(dat, at)).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf::objstm::*;
+
+    fn stream(dat: &[u8]) -> Stream<'_> {
+        object::parse_indirect(dat, 0, &|_| None).unwrap().obj.as_stream().unwrap().clone()
+    }
+
+    #[test]
+    // 目的：オブジェクトストリームのindex番目が借用したまま取り出せるかを確認する
+    fn test_objstm_get() {
+        let dat = b"2 0 obj << /Type /ObjStm /N 2 /First 8 /Length 23 >> stream\n3 0 4 3 [1]<< /A (x) >>\nendstream endobj";
+        let d = decode(&stream(dat)).unwrap();
+        let o = d.get(dat, 1).unwrap();
+        assert_eq!(o.id, ObjRef { num: 4, gen: 0 });
+        let v = o.obj.as_dict().unwrap().get("A").unwrap();
+        assert!(matches!(v, Object::String(Cow::Borrowed(b"x"))));
+        assert_eq!(d.get(dat, 0).unwrap().obj, Object::Array(vec![Object::Integer(1)]));
+        assert_eq!(d.get(dat, 2).err(), Some(PdfError::BadObjectStream { offset: 60 }));
+        assert_eq!(numbers(&stream(dat)), Ok(vec![3, 4]));
+    }
+}
@@ -0,0 +1,193 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+
+use super::{PdfError, XRefEntryKind, XRefTableEntry};
+use super::lexer::{Lexer, Token};
+use super::object::{self, Dict, ObjRef, Object};
+use super::{objstm, xref};
+
+// xrefが読めない・オフセットがずれているファイルのために、本体を先頭から走査してxrefを作り直す。
+//
+//   - "N G obj" の見出しを探し、その位置から実際にオブジェクトが読めたものだけを採る
+//   - 同じ番号が何度も出てきたら後ろにあるもの (追記更新で新しいもの) を採る
+//   - ストリームの中身は読み飛ばす (中に見出しに似たバイト列があっても拾わない)
+//   - オブジェクトストリームの中のオブジェクトは、そのストリームの位置に出てきたものとして扱う
+//   - "trailer" の辞書と相互参照ストリームの辞書を位置の順に重ね、trailerを作る
+// 修復したエントリのentry_dataは、元のバイト列の見出しの部分 ("N G obj") を指す。
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepairReason {
+    // xrefが読めなかった
+    Unreadable(PdfError),
+    // xrefは読めたが、これらの番号のオフセットが "N G obj" を指していなかった
+    BadOffsets(Vec<u32>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairReport {
+    pub reason: RepairReason,
+    // 見つけて読めたオブジェクトの数 (同じ番号の古いものも数える)
+    pub objects: usize,
+    // そのうちオブジェクトストリームの中にあったものの数
+    pub compressed: usize,
+    // trailerに使った辞書の位置 ("trailer" または相互参照ストリームの見出し)
+    pub trailers: Vec<usize>,
+    // どのtrailerにも/Rootが無く、/Type /Catalog のオブジェクトから補った
+    pub root_guessed: bool,
+}
+
+// offsetが "N G obj" の先頭なら、その番号
+pub fn header_at(dat: &[u8], offset: usize) -> Option<ObjRef> {
+    let mut lex = Lexer::new(dat, offset);
+    match (lex.next_token().ok()??, lex.next_token().ok()??, lex.next_token().ok()??) {
+        ((at, Token::Integer(num)), (_, Token::Integer(gen)), (_, Token::Keyword(b"obj"))) if at == offset => {
+            Some(ObjRef { num: num.try_into().ok()?, gen: gen.try_into().ok()? })
+        }
+        _ => None,
+    }
+}
+
+// 使用中のエントリのうち、オフセットがその番号の見出しを指していないもの
+pub fn bad_offsets(dat: &[u8], entries: &[XRefTableEntry]) -> Vec<u32> {
+    entries
+        .iter()
+        .filter(|e| match e.kind {
+            XRefEntryKind::InUse { offset } => header_at(dat, offset) != Some(ObjRef { num: e.obj_num, gen: e.gen }),
+            _ => false,
+        })
+        .map(|e| e.obj_num)
+        .collect()
+}
+
+// 走査して作ったエントリ (番号順) と、重ねたtrailerの辞書 (古い順)。見出しが1つも見つからなければエラー
+pub fn scan(
+    dat: &[u8],
+    reason: RepairReason,
+) -> Result<(Vec<XRefTableEntry<'_>>, Vec<Dict<'_>>, RepairReport), PdfError> {
+    let mut found: BTreeMap<u32, XRefTableEntry> = BTreeMap::new();
+    let mut trailers: Vec<(usize, Dict)> = vec![];
+    let mut report = RepairReport { reason, objects: 0, compressed: 0, trailers: vec![], root_guessed: false };
+
+    let mut pos = 0;
+    while let Some(at) = find(dat, pos, b"obj") {
+        pos = at + 3;
+        let Some(start) = header_start(dat, at) else {
+            continue;
+        };
+        let Ok(o) = object::parse_indirect(dat, start, &|_| None) else {
+            continue;
+        };
+        let head = Cow::Borrowed(&dat[start..at + 3]);
+        report.objects += 1;
+        found.insert(o.id.num, XRefTableEntry::new(o.id.num, o.id.gen, XRefEntryKind::InUse { offset: start }, head.clone()));
+        let Object::Stream(stm) = &o.obj else {
+            continue;
+        };
+        pos = stm.offset + stm.data.len();
+        match stm.dict.get("Type").and_then(Object::as_name) {
+            Some(b"ObjStm") => {
+                // 中身が読めなければ、ストリーム自体だけを採る
+                for (index, num) in objstm::numbers(stm).unwrap_or_default().into_iter().enumerate() {
+                    let kind = XRefEntryKind::Compressed { stream: o.id.num, index: index as u32 };
+                    found.insert(num, XRefTableEntry::new(num, 0, kind, head.clone()));
+                    report.objects += 1;
+                    report.compressed += 1;
+                }
+            }
+            Some(b"XRef") => trailers.push((start, stm.dict.clone())),
+            _ => {}
+        }
+    }
+    if found.is_empty() {
+        return Err(PdfError::NoObjectsFound);
+    }
+
+    let mut pos = 0;
+    while let Some(at) = find(dat, pos, b"trailer") {
+        pos = at + 7;
+        if let Ok(dict) = xref::parse_trailer(dat, at) {
+            trailers.push((at, dict));
+        }
+    }
+    trailers.sort_by_key(|v| v.0);
+    report.trailers = trailers.iter().map(|v| v.0).collect();
+
+    found.entry(0).or_insert_with(|| {
+        XRefTableEntry::new(0, 65535, XRefEntryKind::Free { next: 0 }, Cow::Borrowed(b"0000000000 65535 f\r\n"))
+    });
+    Ok((found.into_values().collect(), trailers.into_iter().map(|v| v.1).collect(), report))
+}
+
+fn find(dat: &[u8], from: usize, word: &[u8]) -> Option<usize> {
+    dat.get(from..)?.windows(word.len()).position(|v| v == word).map(|v| v + from)
+}
+
+// "obj" の位置から戻って "N G " の先頭を探す。"endobj" や数字の途中なら None
+fn header_start(dat: &[u8], at: usize) -> Option<usize> {
+    let digits_before = |end: usize| dat[..end].iter().rev().take_while(|c| c.is_ascii_digit()).count();
+    let ws_before = |end: usize| dat[..end].iter().rev().take_while(|c| xref::is_whitespace(**c)).count();
+    let mut pos = at - ws_before(at);
+    let n = digits_before(pos);
+    if n == 0 {
+        return None;
+    }
+    pos -= n;
+    let n = ws_before(pos);
+    if n == 0 {
+        return None;
+    }
+    pos -= n;
+    let n = digits_before(pos);
+    if n == 0 {
+        return None;
+    }
+    pos -= n;
+    header_at(dat, pos).map(|_| pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf::repair::*;
+
+    #[test]
+    // 目的：見出しを走査し、新しいもの・オブジェクトストリームの中身が採られ、ストリームの中身は無視されるかを確認する
+    fn test_repair_scan() {
+        let dat = b"%PDF-1.5\n\
+            1 0 obj << /Type /Catalog >> endobj\n\
+            2 0 obj (old) endobj\n\
+            3 0 obj << /Length 99 >> stream\n9 0 obj (fake) endobj\nendstream endobj\n\
+            4 0 obj << /Type /ObjStm /N 2 /First 8 /Length 13 >> stream\n5 0 6 2 1 (x)\nendstream endobj\n\
+            trailer << /Size 3 /Root 1 0 R >>\n\
+            2 1 obj (new) endobj\n\
+            10 0 obj 7 endobj 11 0 objx 12 0 obj [\n";
+        let reason = RepairReason::Unreadable(PdfError::StartXRefNotFound);
+        let (entries, trailers, report) = scan(dat, reason.clone()).unwrap();
+        let v: Vec<_> = entries.iter().map(|v| (v.obj_num, v.gen, v.kind)).collect();
+        let offset = |s: &str| dat.windows(s.len()).position(|v| v == s.as_bytes()).unwrap();
+        assert_eq!(v, vec![
+            (0, 65535, XRefEntryKind::Free { next: 0 }),
+            (1, 0, XRefEntryKind::InUse { offset: 9 }),
+            (2, 1, XRefEntryKind::InUse { offset: offset("2 1 obj") }),
+            (3, 0, XRefEntryKind::InUse { offset: offset("3 0 obj") }),
+            (4, 0, XRefEntryKind::InUse { offset: offset("4 0 obj") }),
+            (5, 0, XRefEntryKind::Compressed { stream: 4, index: 0 }),
+            (6, 0, XRefEntryKind::Compressed { stream: 4, index: 1 }),
+            (10, 0, XRefEntryKind::InUse { offset: offset("10 0 obj") }),
+        ]);
+        assert_eq!(&*entries[2].entry_data, b"2 1 obj");
+        assert_eq!(trailers.len(), 1);
+        assert_eq!(trailers[0].get("Size"), Some(&Object::Integer(3)));
+        assert_eq!(report, RepairReport {
+            reason,
+            objects: 8,
+            compressed: 2,
+            trailers: vec![offset("trailer")],
+            root_guessed: false,
+        });
+
+        assert_eq!(scan(b"%PDF-1.4\nendobj obj 1 obj", RepairReason::BadOffsets(vec![])).err(), Some(PdfError::NoObjectsFound));
+        assert_eq!(bad_offsets(dat, &entries), vec![]);
+        let moved = [XRefTableEntry::new(2, 1, XRefEntryKind::InUse { offset: 10 }, Cow::Borrowed(&b""[..]))];
+        assert_eq!(bad_offsets(dat, &moved), vec![2]);
+    }
+}
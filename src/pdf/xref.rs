@@ -0,0 +1,290 @@
+use std::borrow::Cow;
+
+use super::{PdfError, XRefEntryKind, XRefTableEntry};
+use super::lexer::{Lexer, Token};
+use super::object::{self, Dict, Object};
+
+// 従来形式の相互参照表 (xref) を読む。
+//
+//   xref
+//   0 3                      ← サブセクション: 先頭のオブジェクト番号と個数
+//   0000000000 65535 f\r\n   ← エントリは必ず20バイト: オフセット(10桁) 世代(5桁) n/f 改行(2バイト)
+//   0000000017 00000 n\r\n
+//   ...
+//   trailer
+//
+// エラーには問題のあった行の先頭オフセットを付ける。
+//
+// PDF 1.5以降の相互参照ストリーム (/Type /XRef) も読む。
+// 各行は /W で決まる幅の3つの整数 (種別, 値1, 値2) のビッグエンディアンで、/Index のサブセクション順に並ぶ。
+//   種別0: 空き (次の空き番号, 世代)  1: 使用中 (オフセット, 世代)  2: オブジェクトストリーム内 (ストリームの番号, 何番目か)
+
+#[derive(Debug)]
+pub struct XRefSection<'a> {
+    // 出てきた順。同じ番号が重複していてもそのまま
+    pub entries: Vec<XRefTableEntry<'a>>,
+    // "trailer" の位置
+    pub trailer_offset: usize,
+}
+
+const ENTRY_LEN: usize = 20;
+
+pub fn is_whitespace(c: u8) -> bool {
+    matches!(c, b'\0' | b'\t' | b'\n' | b'\x0C' | b'\r' | b' ')
+}
+
+pub fn skip_whitespace(dat: &[u8], mut pos: usize) -> usize {
+    while pos < dat.len() && is_whitespace(dat[pos]) {
+        pos += 1;
+    }
+    pos
+}
+
+// 10進の数字列を読む。(値, 読み終えた位置)
+fn read_uint(dat: &[u8], pos: usize) -> Option<(u64, usize)> {
+    let len = dat[pos.min(dat.len())..].iter().take_while(|c| c.is_ascii_digit()).count();
+    if len == 0 {
+        return None;
+    }
+    let v = std::str::from_utf8(&dat[pos..pos + len]).ok()?.parse().ok()?;
+    Some((v, pos + len))
+}
+
+// 末尾の "startxref" が指すオフセット
+pub fn find_startxref(dat: &[u8]) -> Result<usize, PdfError> {
+    const KEYWORD: &[u8] = b"startxref";
+    let at = dat.windows(KEYWORD.len())
+        .rposition(|v| v == KEYWORD)
+        .ok_or(PdfError::StartXRefNotFound)?;
+    let pos = skip_whitespace(dat, at + KEYWORD.len());
+    match read_uint(dat, pos) {
+        Some((v, _)) if (v as usize) < dat.len() => Ok(v as usize),
+        _ => Err(PdfError::BadStartXRef { offset: pos }),
+    }
+}
+
+pub fn parse_xref_section(dat: &[u8], offset: usize) -> Result<XRefSection<'_>, PdfError> {
+    if !dat.get(offset..).is_some_and(|v| v.starts_with(b"xref")) {
+        return Err(PdfError::XRefNotFound { offset });
+    }
+    let mut entries = vec![];
+    let mut pos = offset + 4;
+    loop {
+        pos = skip_whitespace(dat, pos);
+        if pos >= dat.len() {
+            return Err(PdfError::UnexpectedEof { offset: pos });
+        }
+        if dat[pos..].starts_with(b"trailer") {
+            return Ok(XRefSection { entries, trailer_offset: pos });
+        }
+
+        let (start, count, next) = parse_subsection_header(dat, pos)
+            .ok_or(PdfError::BadSubsection { offset: pos })?;
+        let header = pos;
+        pos = skip_whitespace(dat, next);
+        for i in 0..count {
+            let line = dat.get(pos..pos + ENTRY_LEN).ok_or(PdfError::UnexpectedEof { offset: pos })?;
+            let obj_num = u32::try_from(start + i).map_err(|_| PdfError::BadSubsection { offset: header })?;
+            let (field1, gen, kind) = parse_entry(line).ok_or(PdfError::BadEntry { offset: pos })?;
+            let kind = match kind {
+                b'n' => XRefEntryKind::InUse { offset: field1 as usize },
+                _ => XRefEntryKind::Free { next: field1 },
+            };
+            entries.push(XRefTableEntry::new(obj_num, gen, kind, Cow::Borrowed(line)));
+            pos += ENTRY_LEN;
+        }
+    }
+}
+
+// 相互参照ストリームのエントリと、trailerの代わりになるストリームの辞書
+pub fn parse_xref_stream(dat: &[u8], offset: usize) -> Result<(Vec<XRefTableEntry<'_>>, Dict<'_>), PdfError> {
+    let o = match object::parse_indirect(dat, offset, &|_| None) {
+        Ok(v) => v,
+        Err(PdfError::BadObjectHeader { .. }) => return Err(PdfError::XRefNotFound { offset }),
+        Err(e) => return Err(e),
+    };
+    let bad = PdfError::BadXRefStream { offset };
+    let Object::Stream(stream) = o.obj else {
+        return Err(bad);
+    };
+    let dict = stream.dict.clone();
+    if dict.get("Type").and_then(Object::as_name) != Some(b"XRef") {
+        return Err(bad);
+    }
+    let w: Vec<usize> = dict.get("W").and_then(Object::as_array)
+        .map(|v| v.iter().filter_map(|v| v.as_int()?.try_into().ok()).filter(|v| *v <= 8).collect())
+        .filter(|v: &Vec<usize>| v.len() == 3)
+        .ok_or(bad.clone())?;
+    let row_len: usize = w.iter().sum();
+    let size = dict.get("Size").and_then(Object::as_int).ok_or(bad.clone())?;
+    let index: Vec<i64> = match dict.get("Index") {
+        Some(v) => v.as_array()
+            .map(|v| v.iter().filter_map(Object::as_int).collect())
+            .filter(|v: &Vec<i64>| v.len().is_multiple_of(2))
+            .ok_or(bad.clone())?,
+        None => vec![0, size],
+    };
+    if row_len == 0 || index.iter().any(|v| *v < 0) {
+        return Err(bad);
+    }
+
+    let data_offset = stream.offset;
+    let data = stream.into_decoded()?;
+    let mut entries = vec![];
+    let mut pos = 0;
+    for pair in index.chunks(2) {
+        for i in 0..pair[1] {
+            let row = data.get(pos..pos + row_len)
+                .ok_or(PdfError::UnexpectedEof { offset: data_offset + pos })?;
+            let mut fields = [0u64; 3];
+            let mut at = 0;
+            for (f, width) in fields.iter_mut().zip(&w) {
+                *f = row[at..at + width].iter().fold(0, |acc, b| acc << 8 | *b as u64);
+                at += width;
+            }
+            // 種別の幅が0なら使用中
+            let ty = if w[0] == 0 { 1 } else { fields[0] };
+            let obj_num = pair[0].checked_add(i).and_then(|v| u32::try_from(v).ok()).ok_or(bad.clone())?;
+            let gen = u16::try_from(fields[2]).map_err(|_| bad.clone());
+            let (kind, gen) = match ty {
+                0 => (XRefEntryKind::Free { next: fields[1] }, gen?),
+                1 => (XRefEntryKind::InUse { offset: fields[1] as usize }, gen?),
+                2 => (XRefEntryKind::Compressed {
+                    stream: u32::try_from(fields[1]).map_err(|_| bad.clone())?,
+                    index: u32::try_from(fields[2]).map_err(|_| bad.clone())?,
+                }, 0),
+                // 知らない種別はnullへの参照として扱う
+                _ => {
+                    pos += row_len;
+                    continue;
+                }
+            };
+            let row = match &data {
+                Cow::Borrowed(v) => Cow::Borrowed(&v[pos..pos + row_len]),
+                Cow::Owned(_) => Cow::Owned(row.to_vec()),
+            };
+            entries.push(XRefTableEntry::new(obj_num, gen, kind, row));
+            pos += row_len;
+        }
+    }
+    Ok((entries, dict))
+}
+
+// "trailer" に続く辞書
+pub fn parse_trailer(dat: &[u8], offset: usize) -> Result<Dict<'_>, PdfError> {
+    let mut lex = Lexer::new(dat, offset);
+    if !matches!(lex.next_token()?, Some((_, Token::Keyword(b"trailer")))) {
+        return Err(PdfError::BadTrailer { offset });
+    }
+    match object::parse_object(&mut lex)? {
+        Object::Dict(v) => Ok(v),
+        _ => Err(PdfError::BadTrailer { offset }),
+    }
+}
+
+// "start count" の行。(start, count, 行末の位置)
+fn parse_subsection_header(dat: &[u8], pos: usize) -> Option<(u64, u64, usize)> {
+    let (start, pos) = read_uint(dat, pos)?;
+    let sp = dat[pos..].iter().take_while(|c| **c == b' ').count();
+    if sp == 0 {
+        return None;
+    }
+    let (count, pos) = read_uint(dat, pos + sp)?;
+    let rest = dat[pos..].iter().take_while(|c| **c == b' ').count();
+    match dat.get(pos + rest) {
+        Some(b'\r' | b'\n') => Some((start, count, pos + rest)),
+        _ => None,
+    }
+}
+
+// 20バイトの行。(オフセットまたは次の空き番号, 世代, n/f)
+fn parse_entry(line: &[u8]) -> Option<(u64, u16, u8)> {
+    let digits = |v: &[u8]| v.iter().all(u8::is_ascii_digit);
+    if !digits(&line[0..10]) || line[10] != b' ' || !digits(&line[11..16]) || line[16] != b' ' {
+        return None;
+    }
+    if !matches!(line[17], b'n' | b'f') || !matches!(&line[18..20], b" \r" | b" \n" | b"\r\n") {
+        return None;
+    }
+    let (field1, _) = read_uint(line, 0)?;
+    let (gen, _) = read_uint(line, 11)?;
+    Some((field1, u16::try_from(gen).ok()?, line[17]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pdf::xref::*;
+
+    #[test]
+    // 目的：複数のサブセクションを持つxrefが読めるかを確認する
+    fn test_parse_xref_section() {
+        let dat = b"xref\n0 2\n0000000000 65535 f\r\n0000000015 00000 n\r\n7 1\n0000000099 00002 n \ntrailer\n";
+        let s = parse_xref_section(dat, 0).unwrap();
+        let nums: Vec<_> = s.entries.iter().map(|v| (v.obj_num, v.gen, v.kind)).collect();
+        assert_eq!(nums, vec![
+            (0, 65535, XRefEntryKind::Free { next: 0 }),
+            (1, 0, XRefEntryKind::InUse { offset: 15 }),
+            (7, 2, XRefEntryKind::InUse { offset: 99 }),
+        ]);
+        assert_eq!(&dat[s.trailer_offset..], b"trailer\n");
+    }
+
+    #[test]
+    // 目的：壊れたエントリやサブセクションでその位置がエラーになるかを確認する
+    fn test_parse_xref_section_error() {
+        // 世代が4桁しかない
+        let dat = b"xref\n0 2\n0000000000 65535 f\r\n0000000015 0000 n\r\n\ntrailer\n";
+        assert_eq!(parse_xref_section(dat, 0).err(), Some(PdfError::BadEntry { offset: 29 }));
+        let dat = b"xref\n0 x\n";
+        assert_eq!(parse_xref_section(dat, 0).err(), Some(PdfError::BadSubsection { offset: 5 }));
+        // 個数に対してエントリが足りない
+        let dat = b"xref\n0 2\n0000000000 65535 f\r\n";
+        assert_eq!(parse_xref_section(dat, 0).err(), Some(PdfError::UnexpectedEof { offset: 29 }));
+        assert_eq!(parse_xref_section(b"  xref", 0).err(), Some(PdfError::XRefNotFound { offset: 0 }));
+    }
+
+    #[test]
+    // 目的：/Indexで飛び飛びの番号を持つ相互参照ストリームが読めるかを確認する
+    fn test_parse_xref_stream() {
+        let rows = [0u8, 0, 0, 0xff, 0xff, 1, 0, 0x20, 0, 0, 2, 0, 9, 0, 3];
+        let mut dat = format!("7 0 obj << /Type /XRef /Size 12 /W [1 2 2] /Index [0 1 10 2] /Length {} >> stream\n", rows.len())
+            .into_bytes();
+        dat.extend_from_slice(&rows);
+        dat.extend_from_slice(b"\nendstream endobj");
+        let (entries, dict) = parse_xref_stream(&dat, 0).unwrap();
+        let v: Vec<_> = entries.iter().map(|v| (v.obj_num, v.gen, v.kind, v.entry_type())).collect();
+        assert_eq!(v, vec![
+            (0, 65535, XRefEntryKind::Free { next: 0 }, 0),
+            (10, 0, XRefEntryKind::InUse { offset: 0x20 }, 1),
+            (11, 0, XRefEntryKind::Compressed { stream: 9, index: 3 }, 2),
+        ]);
+        assert_eq!(&*entries[2].entry_data, &rows[10..]);
+        assert_eq!(dict.get("Size"), Some(&Object::Integer(12)));
+
+        let dat = b"7 0 obj << /Type /XRef /Size 1 /W [1 2] /Length 0 >> stream\n\nendstream endobj";
+        assert_eq!(parse_xref_stream(dat, 0).err(), Some(PdfError::BadXRefStream { offset: 0 }));
+        // 開始番号に件数を足すと溢れる /Index
+        let dat = b"7 0 obj << /Type /XRef /Size 1 /W [1 2 2] /Index [9223372036854775807 2] /Length 10 >> stream\n0000000000\nendstream endobj";
+        assert_eq!(parse_xref_stream(dat, 0).err(), Some(PdfError::BadXRefStream { offset: 0 }));
+        let dat = b"7 0 obj << /Type /XRef /Size 1 /W [1 2 2] /Filter /JBIG2Decode /Length 0 >> stream\n\nendstream endobj";
+        assert!(matches!(parse_xref_stream(dat, 0), Err(PdfError::UnsupportedFilter { .. })));
+
+        // よくある形: FlateDecodeで圧縮し、PNGの予測子 (上の行との差) を掛けたもの
+        let mut up = vec![];
+        for (i, row) in rows.chunks(5).enumerate() {
+            up.push(2);
+            up.extend(row.iter().enumerate().map(|(k, b)| b.wrapping_sub(if i == 0 { 0 } else { rows[(i - 1) * 5 + k] })));
+        }
+        let z = crate::pdf::filter::flate::tests::deflate_fixed(&up);
+        let mut dat = format!(
+            "7 0 obj << /Type /XRef /Size 12 /W [1 2 2] /Index [0 1 10 2] /Filter /FlateDecode \
+             /DecodeParms << /Predictor 12 /Columns 5 >> /Length {} >> stream\n",
+            z.len()
+        )
+        .into_bytes();
+        dat.extend_from_slice(&z);
+        dat.extend_from_slice(b"\nendstream endobj");
+        let (flated, _) = parse_xref_stream(&dat, 0).unwrap();
+        assert_eq!(flated, entries);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::name::{self, NameError, NameOptions, NameParts, NameSpans};
+use reading::NameReading;
+
+pub mod format;
+pub mod fuzzy;
+pub mod reading;
+pub mod directory;
+
+// 以前はname/surnameをfull_nameへの&strで持っていたが、
+// full_nameを書き換えると解放済みのStringを指してしまう (init(self: &'me mut Person<'me>) で縛るしかなかった)。
+// 今はfull_nameと各部分の範囲だけを持ち、name/surnameは読む時にfull_nameから借用する。
+// full_nameは非公開で、書き換えはset_full_nameを通すので範囲と食い違うことはない。
+#[derive(Clone, PartialEq, Eq)]
+pub struct Person {
+    full_name: String,
+    spans: NameSpans,
+    opts: NameOptions,
+    // 読み (任意)
+    reading: Option<NameReading>,
+}
+
+impl Person {
+    pub fn new(full_name: String) -> Option<Self> {
+        Person::parse(full_name, &NameOptions::default()).ok()
+    }
+
+    pub fn parse(full_name: String, opts: &NameOptions) -> Result<Self, NameError> {
+        let spans = name::parse_name(&full_name, opts)?;
+        Ok(Person { full_name, spans, opts: *opts, reading: None })
+    }
+
+    pub fn full_name(&self) -> &str {
+        &self.full_name
+    }
+
+    pub fn name(&self) -> &str {
+        &self.full_name[self.spans.given.clone()]
+    }
+
+    pub fn surname(&self) -> &str {
+        &self.full_name[self.spans.family.clone()]
+    }
+
+    // 名・ミドル・姓・suffixをfull_nameから借用したviewとして返す
+    pub fn parts(&self) -> NameParts<'_> {
+        self.spans.parts(&self.full_name)
+    }
+
+    pub fn options(&self) -> &NameOptions {
+        &self.opts
+    }
+
+    // 解釈し直せた場合だけfull_nameと各部分をまとめて差し替える。失敗したら元のまま
+    // 別の名前になるので読みは消す
+    pub fn set_full_name(&mut self, full_name: String) -> Result<(), NameError> {
+        let spans = name::parse_name(&full_name, &self.opts)?;
+        self.full_name = full_name;
+        self.spans = spans;
+        self.reading = None;
+        Ok(())
+    }
+
+    // 解釈のオプションを変えて今のfull_nameを解釈し直す。失敗したら元のまま
+    pub fn set_options(&mut self, opts: NameOptions) -> Result<(), NameError> {
+        self.spans = name::parse_name(&self.full_name, &opts)?;
+        self.opts = opts;
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for Person {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Person")
+            .field("full_name", &self.full_name)
+            .field("name", &self.name())
+            .field("surname", &self.surname())
+            .field("reading", &self.reading)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::person::*;
+
+    #[test]
+    // 目的：set_full_nameでname/surnameが導き直されるかを確認する
+    fn test_person_set_full_name() {
+        let mut p = Person::new("Aiko Hayashi".into()).unwrap();
+        assert_eq!((p.name(), p.surname()), ("Aiko", "Hayashi"));
+        p.set_full_name("Fumika Ito".into()).unwrap();
+        assert_eq!(p.full_name(), "Fumika Ito");
+        assert_eq!((p.name(), p.surname()), ("Fumika", "Ito"));
+    }
+
+    #[test]
+    // 目的：解釈できない名前を渡すとエラーになり、元の値が残るかを確認する
+    fn test_person_set_full_name_error() {
+        let mut p = Person::new("Aiko Hayashi".into()).unwrap();
+        assert_eq!(p.set_full_name("Aiko".into()), Err(NameError::SinglePart("Aiko".into())));
+        assert_eq!(p.full_name(), "Aiko Hayashi");
+        assert_eq!((p.name(), p.surname()), ("Aiko", "Hayashi"));
+    }
+
+    #[test]
+    // 目的：moveやcloneしても各部分が正しく読めるかを確認する
+    fn test_person_move_and_clone() {
+        let people: Vec<Person> = ["Ryuta Hayashi", "Haruka Hayashi"].iter()
+            .map(|v| Person::new(v.to_string()).unwrap())
+            .collect();
+        let mut p = people[1].clone();
+        drop(people);
+        assert_eq!(p.name(), "Haruka");
+        p.set_options(NameOptions::family_first()).unwrap();
+        assert_eq!((p.name(), p.surname()), ("Hayashi", "Haruka"));
+    }
+}
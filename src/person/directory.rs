@@ -0,0 +1,244 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::Person;
+
+// 多数のPersonを持ち、姓・名で引ける索引を付けたもの。
+// 索引のキーは格納したPersonのfull_nameからの借用で、文字列を複製しない。
+//
+// Personの自己参照 (name/surnameがfull_nameを指す) と同じ形だが、こちらは本当に借用したまま持つ。
+//   - full_nameの中身はヒープにあるので、Personがarena (Vec) の伸長で移動してもキーは指す先を失わない
+//   - Personを消す・書き換えるのは remove / update だけで、どちらも先に索引からキーを外す
+//   - キーは同じ姓 (名) の人のうち誰か1人のfull_nameを指す。その人を外すときは、残った人のfull_nameに付け替える
+//   - &mut Person は外に出さない (set_full_nameされるとキーが解放済みの文字列を指してしまう)
+// この約束を守る限り、キーは索引に載っている間ずっと有効なので &'static str として持つ。
+// 外に返す参照はすべて &self の寿命に縛る。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PersonId(usize);
+
+// 姓または名から、その姓 (名) を持つ人へ
+type Index = BTreeMap<&'static str, BTreeSet<PersonId>>;
+
+#[derive(Debug, Default)]
+pub struct PersonDirectory {
+    // arenaより先に宣言して先に破棄する
+    by_family: Index,
+    by_given: Index,
+    // 消した場所はNoneのまま残し、PersonIdを使い回さない
+    arena: Vec<Option<Person>>,
+    len: usize,
+}
+
+impl PersonDirectory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, person: Person) -> PersonId {
+        let id = PersonId(self.arena.len());
+        self.arena.push(Some(person));
+        self.index(id);
+        self.len += 1;
+        id
+    }
+
+    pub fn get(&self, id: PersonId) -> Option<&Person> {
+        self.arena.get(id.0)?.as_ref()
+    }
+
+    pub fn remove(&mut self, id: PersonId) -> Option<Person> {
+        self.get(id)?;
+        self.unindex(id);
+        self.len -= 1;
+        self.arena[id.0].take()
+    }
+
+    // Personを書き換える。書き換えの前に索引から外し、後で載せ直す
+    pub fn update<R>(&mut self, id: PersonId, f: impl FnOnce(&mut Person) -> R) -> Option<R> {
+        self.get(id)?;
+        self.unindex(id);
+        let ret = f(self.arena[id.0].as_mut().unwrap());
+        self.index(id);
+        Some(ret)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (PersonId, &Person)> {
+        self.arena.iter().enumerate().filter_map(|(i, v)| Some((PersonId(i), v.as_ref()?)))
+    }
+
+    pub fn by_surname(&self, surname: &str) -> impl Iterator<Item = (PersonId, &Person)> {
+        self.lookup(&self.by_family, surname)
+    }
+
+    pub fn by_given_name(&self, given: &str) -> impl Iterator<Item = (PersonId, &Person)> {
+        self.lookup(&self.by_given, given)
+    }
+
+    // 姓か名がprefixで始まる人。PersonIdの順
+    pub fn search_prefix(&self, prefix: &str) -> impl Iterator<Item = (PersonId, &Person)> {
+        let ids: BTreeSet<PersonId> = [&self.by_family, &self.by_given].into_iter()
+            .flat_map(|index| index.range(prefix..).take_while(|(k, _)| k.starts_with(prefix)))
+            .flat_map(|(_, ids)| ids.iter().copied())
+            .collect();
+        ids.into_iter().map(|id| (id, self.get(id).unwrap()))
+    }
+
+    // 索引に載っている姓。辞書順
+    pub fn surnames(&self) -> impl Iterator<Item = &str> {
+        self.by_family.keys().copied()
+    }
+
+    fn lookup<'s>(
+        &'s self,
+        index: &'s Index,
+        key: &str,
+    ) -> impl Iterator<Item = (PersonId, &'s Person)> {
+        index.get(key)
+            .into_iter()
+            .flatten()
+            .map(|id| (*id, self.get(*id).unwrap()))
+    }
+
+    fn index(&mut self, id: PersonId) {
+        let p = self.arena[id.0].as_ref().unwrap();
+        // 上の約束により、キーは索引から外すまでfull_nameの中を指し続ける
+        let (family, given) = unsafe { (extend(p.surname()), extend(p.name())) };
+        self.by_family.entry(family).or_default().insert(id);
+        self.by_given.entry(given).or_default().insert(id);
+    }
+
+    fn unindex(&mut self, id: PersonId) {
+        unkey(&mut self.by_family, &self.arena, id, Person::surname);
+        unkey(&mut self.by_given, &self.arena, id, Person::name);
+    }
+}
+
+// indexからidを外す。外したキーはidのfull_nameを指しているかもしれないので、残った人のものに付け替える
+fn unkey(index: &mut Index, arena: &[Option<Person>], id: PersonId, part: fn(&Person) -> &str) {
+    let Some(mut ids) = index.remove(part(arena[id.0].as_ref().unwrap())) else {
+        return;
+    };
+    ids.remove(&id);
+    if let Some(rest) = ids.first() {
+        let key = part(arena[rest.0].as_ref().unwrap());
+        index.insert(unsafe { extend(key) }, ids);
+    }
+}
+
+unsafe fn extend(s: &str) -> &'static str {
+    &*(s as *const str)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::person::directory::*;
+
+    fn directory(names: &[&str]) -> (PersonDirectory, Vec<PersonId>) {
+        let mut d = PersonDirectory::new();
+        let ids = names.iter().map(|v| d.insert(Person::new(v.to_string()).unwrap())).collect();
+        (d, ids)
+    }
+
+    fn names<'a>(it: impl Iterator<Item = (PersonId, &'a Person)>) -> Vec<&'a str> {
+        it.map(|(_, p)| p.full_name()).collect()
+    }
+
+    // 索引の中身がarenaから作り直したものと一致するか
+    fn assert_consistent(d: &PersonDirectory) {
+        let mut family: BTreeMap<&str, BTreeSet<PersonId>> = BTreeMap::new();
+        let mut given: BTreeMap<&str, BTreeSet<PersonId>> = BTreeMap::new();
+        for (id, p) in d.iter() {
+            family.entry(p.surname()).or_default().insert(id);
+            given.entry(p.name()).or_default().insert(id);
+        }
+        assert_eq!(d.by_family, family);
+        assert_eq!(d.by_given, given);
+        // キーは載っている人のfull_nameの中を指す
+        for index in [&d.by_family, &d.by_given] {
+            for (k, ids) in index {
+                assert!(ids.iter().any(|id| d.get(*id).unwrap().full_name().as_bytes().as_ptr_range().contains(&k.as_ptr())));
+            }
+        }
+        assert_eq!(d.len(), d.iter().count());
+    }
+
+    #[test]
+    // 目的：姓・名で引けて、arenaが伸びてもキーが正しいままかを確認する
+    fn test_person_directory_lookup() {
+        let (mut d, ids) = directory(&["Ryuta Hayashi", "Haruka Hayashi", "Aiko Sato", "Hayashi, Aiko"]);
+        // arenaの再確保を起こす
+        for i in 0..100 {
+            d.insert(Person::new(format!("Taro Yamada{}", i)).unwrap());
+        }
+        assert_eq!(names(d.by_surname("Hayashi")), vec!["Ryuta Hayashi", "Haruka Hayashi", "Hayashi, Aiko"]);
+        assert_eq!(names(d.by_given_name("Aiko")), vec!["Aiko Sato", "Hayashi, Aiko"]);
+        assert_eq!(names(d.by_surname("Ito")), Vec::<&str>::new());
+        assert_eq!(d.get(ids[2]).unwrap().full_name(), "Aiko Sato");
+        assert_eq!(d.len(), 104);
+        assert_consistent(&d);
+    }
+
+    #[test]
+    // 目的：姓・名の前方一致で検索できるかを確認する
+    fn test_person_directory_prefix() {
+        let (d, _) = directory(&["Ryuta Hayashi", "Haruka Hayashi", "Aiko Sato", "Hanako Yamada", "林 竜太"]);
+        assert_eq!(names(d.search_prefix("Ha")), vec!["Ryuta Hayashi", "Haruka Hayashi", "Hanako Yamada"]);
+        assert_eq!(names(d.search_prefix("Hay")), vec!["Ryuta Hayashi", "Haruka Hayashi"]);
+        assert_eq!(names(d.search_prefix("林")), vec!["林 竜太"]);
+        assert_eq!(d.surnames().collect::<Vec<_>>(), vec!["Hayashi", "Sato", "Yamada", "林"]);
+    }
+
+    #[test]
+    // 目的：削除・書き換えの後も索引が食い違わないかを確認する
+    fn test_person_directory_remove_and_update() {
+        let (mut d, ids) = directory(&["Ryuta Hayashi", "Haruka Hayashi", "Aiko Sato"]);
+        let removed = d.remove(ids[0]).unwrap();
+        assert_eq!(removed.full_name(), "Ryuta Hayashi");
+        assert!(d.remove(ids[0]).is_none());
+        assert_eq!(names(d.by_surname("Hayashi")), vec!["Haruka Hayashi"]);
+        assert_consistent(&d);
+
+        d.update(ids[2], |p| p.set_full_name("Aiko Ito".into())).unwrap().unwrap();
+        assert_eq!(names(d.by_surname("Ito")), vec!["Aiko Ito"]);
+        assert!(d.surnames().all(|v| v != "Sato"));
+        // 解釈に失敗しても元の名前で載ったまま
+        assert!(d.update(ids[1], |p| p.set_full_name("Haruka".into())).unwrap().is_err());
+        assert_eq!(names(d.by_given_name("Haruka")), vec!["Haruka Hayashi"]);
+        assert!(d.update(ids[0], |_| ()).is_none());
+        assert_consistent(&d);
+
+        // 新しいidは使い回さない
+        let id = d.insert(Person::new("Ryuta Hayashi".into()).unwrap());
+        assert_ne!(id, ids[0]);
+        assert_consistent(&d);
+    }
+
+    #[test]
+    // 目的：キーを持っていた人を消した・書き換えた後も、索引のキーが残った人の名前から読めるかを確認する
+    fn test_person_directory_rekey() {
+        let (mut d, ids) = directory(&["Ryuta Hayashi", "Haruka Hayashi", "Ryuta Sato"]);
+        drop(d.remove(ids[0]).unwrap());
+        // 解放された領域を別の文字列で埋める
+        let junk: Vec<String> = (0..100).map(|i| format!("Xxxxx Yyyyyyy{}", i)).collect();
+        assert_eq!(d.surnames().collect::<Vec<_>>(), vec!["Hayashi", "Sato"]);
+        assert_eq!(names(d.by_surname("Hayashi")), vec!["Haruka Hayashi"]);
+        assert_eq!(names(d.by_given_name("Ryuta")), vec!["Ryuta Sato"]);
+        assert_consistent(&d);
+
+        d.insert(Person::new("Aiko Hayashi".into()).unwrap());
+        d.update(ids[1], |p| p.set_full_name("Haruka Ito".into())).unwrap().unwrap();
+        let junk2: Vec<String> = (0..100).map(|i| format!("Zzzzz Wwwwwww{}", i)).collect();
+        assert_eq!(names(d.by_surname("Hayashi")), vec!["Aiko Hayashi"]);
+        assert_eq!(d.surnames().collect::<Vec<_>>(), vec!["Hayashi", "Ito", "Sato"]);
+        assert_consistent(&d);
+        assert_eq!(junk.len() + junk2.len(), 200);
+    }
+}
@@ -0,0 +1,260 @@
+use std::str::FromStr;
+use thiserror::Error;
+
+use crate::name::NameParts;
+use super::Person;
+
+// 書式指定でPersonを文字列にする。UIと宛名書きで同じ実装を使うため。
+//
+// 書式は "{given} {family}" のようにフィールドと文字列を並べたもの。
+//   フィールド: {given} {middle} {family} {suffix}
+//   修飾子:     {given:i} → 頭文字 ("R.")、{family:u} → 大文字、{given:iu} のように重ねられる
+//   {{ と }} は波括弧そのもの
+// ミドルネームなど空のフィールドは、その左 (先頭なら右) の区切り文字 (空白・カンマ・中黒) ごと消える。
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum FormatError {
+    #[error("unknown field {name:?} at offset {offset}")]
+    UnknownField { offset: usize, name: String },
+    #[error("unknown modifier {modifier:?} at offset {offset}")]
+    UnknownModifier { offset: usize, modifier: char },
+    #[error("unclosed '{{' at offset {offset}")]
+    Unclosed { offset: usize },
+    #[error("unmatched '}}' at offset {offset}")]
+    Unmatched { offset: usize },
+}
+
+// よく使う書式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameStyle {
+    // Ryuta Hayashi
+    Western,
+    // Hayashi Ryuta
+    FamilyFirst,
+    // Hayashi, Ryuta
+    SurnameComma,
+    // R. Hayashi
+    Initials,
+    // Ryuta HAYASHI (公式書類向け)
+    Official,
+    // Hayashi-san
+    San,
+    // 林 竜太 様
+    Sama,
+}
+
+impl NameStyle {
+    pub fn spec(&self) -> &'static str {
+        match self {
+            NameStyle::Western => "{given} {middle} {family} {suffix}",
+            NameStyle::FamilyFirst => "{family} {given} {middle}",
+            NameStyle::SurnameComma => "{family}, {given} {middle}",
+            NameStyle::Initials => "{given:i} {middle:i} {family}",
+            NameStyle::Official => "{given} {middle} {family:u} {suffix}",
+            NameStyle::San => "{family}-san",
+            NameStyle::Sama => "{family} {given} 様",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    Given,
+    Middle,
+    Family,
+    Suffix,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Piece {
+    Literal(String),
+    Field { field: Field, initial: bool, upper: bool },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameFormat {
+    pieces: Vec<Piece>,
+}
+
+impl FromStr for NameFormat {
+    type Err = FormatError;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut pieces = vec![];
+        let mut lit = String::new();
+        let mut it = spec.char_indices().peekable();
+        while let Some((i, c)) = it.next() {
+            match c {
+                '{' if matches!(it.peek(), Some((_, '{'))) => {
+                    it.next();
+                    lit.push('{');
+                }
+                '}' if matches!(it.peek(), Some((_, '}'))) => {
+                    it.next();
+                    lit.push('}');
+                }
+                '}' => return Err(FormatError::Unmatched { offset: i }),
+                '{' => {
+                    let Some(len) = spec[i..].find('}') else {
+                        return Err(FormatError::Unclosed { offset: i });
+                    };
+                    let body = &spec[i + 1..i + len];
+                    while it.next_if(|(j, _)| *j < i + len + 1).is_some() {}
+                    if !lit.is_empty() {
+                        pieces.push(Piece::Literal(std::mem::take(&mut lit)));
+                    }
+                    pieces.push(parse_field(i, body)?);
+                }
+                c => lit.push(c),
+            }
+        }
+        if !lit.is_empty() {
+            pieces.push(Piece::Literal(lit));
+        }
+        Ok(NameFormat { pieces })
+    }
+}
+
+fn parse_field(offset: usize, body: &str) -> Result<Piece, FormatError> {
+    let (name, mods) = body.split_once(':').unwrap_or((body, ""));
+    let field = match name.trim() {
+        "given" => Field::Given,
+        "middle" => Field::Middle,
+        "family" => Field::Family,
+        "suffix" => Field::Suffix,
+        _ => return Err(FormatError::UnknownField { offset, name: name.to_owned() }),
+    };
+    let (mut initial, mut upper) = (false, false);
+    for m in mods.chars() {
+        match m {
+            'i' => initial = true,
+            'u' => upper = true,
+            modifier => return Err(FormatError::UnknownModifier { offset, modifier }),
+        }
+    }
+    Ok(Piece::Field { field, initial, upper })
+}
+
+impl NameFormat {
+    pub fn render(&self, parts: &NameParts) -> String {
+        let rendered: Vec<(bool, String)> = self.pieces.iter()
+            .map(|p| match p {
+                Piece::Literal(v) => (true, v.clone()),
+                Piece::Field { field, initial, upper } => {
+                    let v = match field {
+                        Field::Given => Some(parts.given),
+                        Field::Middle => parts.middle,
+                        Field::Family => Some(parts.family),
+                        Field::Suffix => parts.suffix,
+                    };
+                    (false, v.map(|v| render_value(v, *initial, *upper)).unwrap_or_default())
+                }
+            })
+            .collect();
+
+        // 空のフィールドに隣接する区切り文字を消す
+        let mut keep = vec![true; rendered.len()];
+        for (i, (is_lit, v)) in rendered.iter().enumerate() {
+            if *is_lit || !v.is_empty() {
+                continue;
+            }
+            let left = (0..i).rev().find(|j| keep[*j] && !(rendered[*j].0 && rendered[*j].1.is_empty()));
+            let right = (i + 1..rendered.len()).find(|j| keep[*j]);
+            match (left, right) {
+                (Some(j), _) if rendered[j].0 && is_separator(&rendered[j].1) => keep[j] = false,
+                (None, Some(j)) if rendered[j].0 && is_separator(&rendered[j].1) => keep[j] = false,
+                _ => {}
+            }
+        }
+        rendered.into_iter()
+            .zip(keep)
+            .filter_map(|((_, v), k)| k.then_some(v))
+            .collect::<String>()
+            .trim()
+            .to_owned()
+    }
+}
+
+fn render_value(v: &str, initial: bool, upper: bool) -> String {
+    let v = if initial {
+        // 複数語のミドルネームはそれぞれ頭文字にする
+        v.split_whitespace()
+            .filter_map(|w| w.chars().next())
+            .map(|c| if c.is_alphabetic() && !crate::name::is_cjk(c) {
+                format!("{}.", c)
+            } else {
+                c.to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    } else {
+        v.to_owned()
+    };
+    if upper { v.to_uppercase() } else { v }
+}
+
+fn is_separator(v: &str) -> bool {
+    v.chars().all(|c| c.is_whitespace() || c == ',' || c == '・')
+}
+
+impl Person {
+    pub fn format(&self, fmt: &NameFormat) -> String {
+        fmt.render(&self.parts())
+    }
+
+    pub fn format_spec(&self, spec: &str) -> Result<String, FormatError> {
+        Ok(self.format(&spec.parse()?))
+    }
+
+    pub fn format_style(&self, style: NameStyle) -> String {
+        // 組み込みの書式は必ず解釈できる
+        self.format_spec(style.spec()).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::person::format::*;
+    use crate::name::NameOptions;
+
+    #[test]
+    // 目的：組み込みの書式で正しく表示されるかを確認する
+    fn test_person_format_style() {
+        let p = Person::new("Ryuta Hayashi".into()).unwrap();
+        assert_eq!(p.format_style(NameStyle::Western), "Ryuta Hayashi");
+        assert_eq!(p.format_style(NameStyle::FamilyFirst), "Hayashi Ryuta");
+        assert_eq!(p.format_style(NameStyle::SurnameComma), "Hayashi, Ryuta");
+        assert_eq!(p.format_style(NameStyle::Initials), "R. Hayashi");
+        assert_eq!(p.format_style(NameStyle::Official), "Ryuta HAYASHI");
+        assert_eq!(p.format_style(NameStyle::San), "Hayashi-san");
+
+        let p = Person::parse("林　竜太".into(), &NameOptions::default()).unwrap();
+        assert_eq!(p.format_style(NameStyle::Sama), "林 竜太 様");
+        assert_eq!(p.format_spec("{family}{given}様").unwrap(), "林竜太様");
+    }
+
+    #[test]
+    // 目的：ミドルネームやsuffixがある場合・ない場合で区切りが正しいかを確認する
+    fn test_person_format_optional_parts() {
+        let p = Person::new("John Ronald Reuel Tolkien".into()).unwrap();
+        assert_eq!(p.format_style(NameStyle::Initials), "J. R. R. Tolkien");
+        assert_eq!(p.format_spec("{middle} {family}").unwrap(), "Ronald Reuel Tolkien");
+        let p = Person::new("Martin King Jr.".into()).unwrap();
+        assert_eq!(p.format_spec("{family}, {given}, {suffix}").unwrap(), "King, Martin, Jr.");
+        assert_eq!(p.format_spec("{middle} {family}").unwrap(), "King");
+        let p = Person::new("Martin King".into()).unwrap();
+        assert_eq!(p.format_spec("{family}, {given}, {suffix}").unwrap(), "King, Martin");
+    }
+
+    #[test]
+    // 目的：不正な書式がオフセット付きのエラーになるかを確認する
+    fn test_name_format_error() {
+        assert_eq!("{{{given}}}".parse::<NameFormat>().map(|_| ()), Ok(()));
+        assert_eq!("{given} {nick}".parse::<NameFormat>(),
+            Err(FormatError::UnknownField { offset: 8, name: "nick".to_owned() }));
+        assert_eq!("{given:x}".parse::<NameFormat>(),
+            Err(FormatError::UnknownModifier { offset: 0, modifier: 'x' }));
+        assert_eq!("{given".parse::<NameFormat>(), Err(FormatError::Unclosed { offset: 0 }));
+        assert_eq!("given}".parse::<NameFormat>(), Err(FormatError::Unmatched { offset: 5 }));
+    }
+}
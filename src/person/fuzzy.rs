@@ -0,0 +1,273 @@
+use crate::name::kana::{self, Romaji};
+use super::Person;
+
+// 名前の揺れを吸収してPersonを比較し、重複らしきものをまとめる。
+//
+// 比較の前に各部分を正規化する:
+//   - かなはヘボン式のローマ字に、全角英数字・全角空白を半角に、大文字を小文字に
+//   - ダイアクリティカルマークを外す ("Ryūta" → "ryuta")
+//   - ローマ字の揺れを寄せる: 訓令式→ヘボン式 (si→shi, tu→tsu ...)、長音 (ou, oo, uu, oh → o/u)、撥音 (mb → nb)
+// その上で姓・名それぞれの編集距離から類似度 (0.0〜1.0) を出す。
+// 姓と名が入れ替わっている場合 ("Hayashi Ryuta" と "Ryuta Hayashi") も比較する。
+// 漢字で書かれた部分は、読みがあれば読みで比較する ("林 竜太" (はやし りゅうた) と "Ryuta Hayashi")。
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    // 渡したスライス中の添字 (昇順)
+    pub members: Vec<usize>,
+    // クラスタ内で一致とみなした組の類似度のうち最も低いもの
+    pub score: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PersonMatcher {
+    // これ以上の類似度なら同一人物とみなす
+    pub threshold: f64,
+}
+
+impl Default for PersonMatcher {
+    fn default() -> Self {
+        PersonMatcher { threshold: 0.85 }
+    }
+}
+
+impl PersonMatcher {
+    pub fn new(threshold: f64) -> Self {
+        PersonMatcher { threshold }
+    }
+
+    pub fn score(&self, a: &Person, b: &Person) -> f64 {
+        let ((ag, af), (bg, bf)) = (comparable(a), comparable(b));
+        let straight = (part_similarity(&ag, &bg) + part_similarity(&af, &bf)) / 2.0;
+        let swapped = (part_similarity(&ag, &bf) + part_similarity(&af, &bg)) / 2.0;
+        straight.max(swapped)
+    }
+
+    pub fn is_match(&self, a: &Person, b: &Person) -> bool {
+        self.score(a, b) >= self.threshold
+    }
+
+    // 2人以上からなるクラスタだけを返す
+    pub fn clusters(&self, people: &[Person]) -> Vec<Cluster> {
+        let n = people.len();
+        let mut parent: Vec<usize> = (0..n).collect();
+        let mut link = vec![f64::INFINITY; n];
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+
+        for i in 0..n {
+            for j in i + 1..n {
+                let s = self.score(&people[i], &people[j]);
+                if s < self.threshold {
+                    continue;
+                }
+                let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                let (lo, hi) = (ri.min(rj), ri.max(rj));
+                link[lo] = link[lo].min(link[hi]).min(s);
+                parent[hi] = lo;
+            }
+        }
+
+        let mut clusters: Vec<Cluster> = vec![];
+        for i in 0..n {
+            let r = root(&mut parent, i);
+            match clusters.iter_mut().find(|c| c.members[0] == r) {
+                Some(c) => c.members.push(i),
+                None => clusters.push(Cluster { members: vec![i], score: link[r] }),
+            }
+        }
+        clusters.retain(|c| c.members.len() > 1);
+        clusters
+    }
+}
+
+// 正規化した (名, 姓)
+fn comparable(p: &Person) -> (String, String) {
+    let parts = p.parts();
+    let pick = |v: &str, r: Option<&str>| match r {
+        Some(r) if !v.is_ascii() && !kana::is_kana(v) => normalize(r),
+        _ => normalize(v),
+    };
+    (pick(parts.given, p.reading().map(|r| r.given())), pick(parts.family, p.reading().map(|r| r.family())))
+}
+
+// 頭文字だけの名 ("R.") は、頭文字が一致すれば似ているとみなす
+fn part_similarity(a: &str, b: &str) -> f64 {
+    let (la, lb) = (a.chars().count(), b.chars().count());
+    if la == 0 && lb == 0 {
+        return 1.0;
+    }
+    if la.min(lb) == 1 && la != lb {
+        return if a.chars().next() == b.chars().next() { 0.8 } else { 0.0 };
+    }
+    1.0 - levenshtein(a, b) as f64 / la.max(lb) as f64
+}
+
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            cur[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(cur[j] + 1);
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+// 比較用に名前の一部を正規化する
+pub fn normalize(s: &str) -> String {
+    let folded: String = kana::to_romaji(s, Romaji::Hepburn).chars()
+        .filter_map(|c| {
+            let c = match c {
+                '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0).unwrap(),
+                '\u{3000}' => ' ',
+                c => c,
+            };
+            let c = strip_diacritic(c);
+            (c.is_alphanumeric()).then_some(c)
+        })
+        .flat_map(char::to_lowercase)
+        .collect();
+    romaji_skeleton(&folded)
+}
+
+fn strip_diacritic(c: char) -> char {
+    match c {
+        'ā' | 'á' | 'à' | 'â' | 'ä' | 'ã' | 'å' => 'a',
+        'Ā' | 'Á' | 'À' | 'Â' | 'Ä' | 'Ã' | 'Å' => 'A',
+        'ē' | 'é' | 'è' | 'ê' | 'ë' => 'e',
+        'Ē' | 'É' | 'È' | 'Ê' | 'Ë' => 'E',
+        'ī' | 'í' | 'ì' | 'î' | 'ï' => 'i',
+        'Ī' | 'Í' | 'Ì' | 'Î' | 'Ï' => 'I',
+        'ō' | 'ó' | 'ò' | 'ô' | 'ö' | 'õ' | 'ø' => 'o',
+        'Ō' | 'Ó' | 'Ò' | 'Ô' | 'Ö' | 'Õ' | 'Ø' => 'O',
+        'ū' | 'ú' | 'ù' | 'û' | 'ü' => 'u',
+        'Ū' | 'Ú' | 'Ù' | 'Û' | 'Ü' => 'U',
+        'ñ' => 'n',
+        'Ñ' => 'N',
+        'ç' => 'c',
+        'Ç' => 'C',
+        c => c,
+    }
+}
+
+// ローマ字表記の揺れを寄せる。ASCII以外 (漢字など) はそのまま
+fn romaji_skeleton(s: &str) -> String {
+    if !s.is_ascii() {
+        return s.to_owned();
+    }
+    const RULES: &[(&str, &str)] = &[
+        // 訓令式・日本式 → ヘボン式
+        ("sya", "sha"), ("syu", "shu"), ("syo", "sho"),
+        ("tya", "cha"), ("tyu", "chu"), ("tyo", "cho"),
+        ("zya", "ja"), ("zyu", "ju"), ("zyo", "jo"),
+        ("si", "shi"), ("ti", "chi"), ("tu", "tsu"), ("hu", "fu"), ("zi", "ji"), ("di", "ji"), ("du", "zu"),
+        // 撥音
+        ("mb", "nb"), ("mp", "np"), ("mm", "nm"),
+    ];
+    let mut out = String::with_capacity(s.len());
+    let b = s.as_bytes();
+    let mut i = 0;
+    'outer: while i < b.len() {
+        // 既にヘボン式の "shi" "shu" などを二重に変換しない
+        for hep in ["sh", "ch", "ts"] {
+            if s[i..].starts_with(hep) {
+                out.push_str(hep);
+                i += hep.len();
+                continue 'outer;
+            }
+        }
+        for (from, to) in RULES {
+            if s[i..].starts_with(from) {
+                out.push_str(to);
+                i += from.len();
+                continue 'outer;
+            }
+        }
+        out.push(b[i] as char);
+        i += 1;
+    }
+
+    // 長音: ou/oo/oh → o、uu → u、aa/ii/ee → a/i/e (ohの後が母音なら長音ではない)
+    let b: Vec<char> = out.chars().collect();
+    let mut ret = String::with_capacity(b.len());
+    let mut i = 0;
+    while i < b.len() {
+        let c = b[i];
+        let next = b.get(i + 1).copied();
+        let after = b.get(i + 2).copied();
+        ret.push(c);
+        let long = match (c, next) {
+            ('o', Some('u')) | ('o', Some('o')) | ('u', Some('u'))
+            | ('a', Some('a')) | ('i', Some('i')) | ('e', Some('e')) => true,
+            ('o', Some('h')) => !matches!(after, Some('a' | 'i' | 'u' | 'e' | 'o')),
+            _ => false,
+        };
+        i += if long { 2 } else { 1 };
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::person::fuzzy::*;
+    use crate::person::reading::NameReading;
+
+    fn person(s: &str) -> Person {
+        Person::new(s.into()).unwrap()
+    }
+
+    #[test]
+    // 目的：表記の揺れが正規化で吸収されるかを確認する
+    fn test_normalize() {
+        assert_eq!(normalize("Ryūta"), "ryuta");
+        assert_eq!(normalize("Ryuuta"), "ryuta");
+        assert_eq!(normalize("ＨＡＹＡＳＨＩ"), "hayashi");
+        assert_eq!(normalize("Hayasi"), "hayashi");
+        assert_eq!(normalize("Ohno"), normalize("Ono"));
+        assert_eq!(normalize("Satou"), normalize("Satō"));
+        assert_eq!(normalize("Nanba"), normalize("Namba"));
+        assert_eq!(normalize("Syuhei"), normalize("Shuhei"));
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    // 目的：似た名前の類似度が高く、違う名前は低くなるかを確認する
+    fn test_person_matcher_score() {
+        let m = PersonMatcher::default();
+        assert_eq!(m.score(&person("Ryuta Hayashi"), &person("Ryūta HAYASHI")), 1.0);
+        assert_eq!(m.score(&person("Ryuta Hayashi"), &person("Hayashi, Ryuta")), 1.0);
+        assert!(m.is_match(&person("Ryuta Hayashi"), &person("Ryuta Hayashl")));
+        assert!(m.is_match(&person("Ryuta Hayashi"), &person("R. Hayashi")));
+        assert!(!m.is_match(&person("Ryuta Hayashi"), &person("Haruka Hayashi")));
+        assert!(!m.is_match(&person("Ryuta Hayashi"), &person("Aiko Sato")));
+
+        // かなはローマ字にして、漢字は読みで比べる
+        assert_eq!(m.score(&person("Ryuta Hayashi"), &person("ハヤシ リュウタ")), 1.0);
+        let kanji = person("林 竜太");
+        assert!(!m.is_match(&person("Ryuta Hayashi"), &kanji));
+        let kanji = kanji.with_reading(NameReading::new("はやし", "りゅうた").unwrap());
+        assert_eq!(m.score(&person("Ryuta Hayashi"), &kanji), 1.0);
+    }
+
+    #[test]
+    // 目的：重複らしきPersonがクラスタにまとまるかを確認する
+    fn test_person_matcher_clusters() {
+        let people: Vec<Person> = [
+            "Ryuta Hayashi", "Aiko Hayashi", "RYŪTA HAYASHI", "Aiko Satou", "Hayasi, Ryuuta", "Aiko Sato",
+        ].iter().map(|v| person(v)).collect();
+        let clusters = PersonMatcher::default().clusters(&people);
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].members, vec![0, 2, 4]);
+        assert_eq!(clusters[0].score, 1.0);
+        assert_eq!(clusters[1].members, vec![3, 5]);
+    }
+}
@@ -0,0 +1,120 @@
+use std::cmp::Ordering;
+
+use crate::name::kana::{self, KanaError, Romaji};
+use super::Person;
+
+// 名前の読み。かなで入力した名前とローマ字の名前を同じように照合・並べ替えるため。
+// ひらがな・カタカナ・ローマ字のどれで渡してもひらがなで持つ。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameReading {
+    family: String,
+    given: String,
+}
+
+impl NameReading {
+    pub fn new(family: &str, given: &str) -> Result<Self, KanaError> {
+        Ok(NameReading { family: to_reading(family)?, given: to_reading(given)? })
+    }
+
+    pub fn family(&self) -> &str {
+        &self.family
+    }
+
+    pub fn given(&self) -> &str {
+        &self.given
+    }
+
+    // (姓, 名) のカタカナ
+    pub fn katakana(&self) -> (String, String) {
+        (kana::to_katakana(&self.family), kana::to_katakana(&self.given))
+    }
+
+    // (姓, 名) のローマ字
+    pub fn romaji(&self, system: Romaji) -> (String, String) {
+        (kana::to_romaji(&self.family, system), kana::to_romaji(&self.given, system))
+    }
+}
+
+fn to_reading(s: &str) -> Result<String, KanaError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(KanaError::Empty);
+    }
+    if kana::is_kana(s) {
+        return Ok(kana::to_hiragana(s));
+    }
+    kana::from_romaji(s)
+}
+
+impl Person {
+    pub fn reading(&self) -> Option<&NameReading> {
+        self.reading.as_ref()
+    }
+
+    pub fn set_reading(&mut self, reading: Option<NameReading>) {
+        self.reading = reading;
+    }
+
+    pub fn with_reading(mut self, reading: NameReading) -> Self {
+        self.reading = Some(reading);
+        self
+    }
+
+    // 並べ替えに使う (姓, 名) のひらがな。
+    // 読みがあればそれを、なければかなやローマ字で書かれた名前から作る。漢字だけの名前で読みがなければNone
+    pub fn reading_key(&self) -> Option<(String, String)> {
+        if let Some(r) = &self.reading {
+            return Some((r.family.clone(), r.given.clone()));
+        }
+        let parts = self.parts();
+        Some((to_reading(parts.family).ok()?, to_reading(parts.given).ok()?))
+    }
+}
+
+// 読みの五十音順 (姓 → 名) で比べる。読みの分からない名前は後ろに回し、full_nameで比べる
+pub fn by_reading(a: &Person, b: &Person) -> Ordering {
+    let key = match (a.reading_key(), b.reading_key()) {
+        (Some(ka), Some(kb)) => kana::gojuon_cmp(&ka.0, &kb.0).then_with(|| kana::gojuon_cmp(&ka.1, &kb.1)),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+    key.then_with(|| a.full_name().cmp(b.full_name()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::person::reading::*;
+
+    fn person(s: &str) -> Person {
+        Person::new(s.into()).unwrap()
+    }
+
+    #[test]
+    // 目的：かな・ローマ字どちらで渡しても読みがひらがなで持たれるかを確認する
+    fn test_name_reading() {
+        let r = NameReading::new("ハヤシ", "りゅうた").unwrap();
+        assert_eq!((r.family(), r.given()), ("はやし", "りゅうた"));
+        assert_eq!(r.katakana(), ("ハヤシ".to_owned(), "リュウタ".to_owned()));
+        assert_eq!(r.romaji(Romaji::Kunrei), ("hayasi".to_owned(), "ryuuta".to_owned()));
+        assert_eq!(NameReading::new("Hayashi", "Ryūta"), Ok(r));
+        assert_eq!(NameReading::new("林", "りゅうた"), Err(KanaError::Unexpected { offset: 0, found: '林' }));
+        assert_eq!(NameReading::new("", "りゅうた"), Err(KanaError::Empty));
+    }
+
+    #[test]
+    // 目的：読みのあるPersonとローマ字のPersonが五十音順に並ぶかを確認する
+    fn test_by_reading() {
+        let mut people = [
+            person("Aiko Sato"),
+            person("林 竜太").with_reading(NameReading::new("はやし", "りゅうた").unwrap()),
+            person("John Smith"),
+            person("イトウ ケイ"),
+            person("Haruka Hayashi"),
+            person("馬場 一郎"),
+        ];
+        people.sort_by(by_reading);
+        let names: Vec<&str> = people.iter().map(|p| p.full_name()).collect();
+        assert_eq!(names, ["イトウ ケイ", "Aiko Sato", "Haruka Hayashi", "林 竜太", "John Smith", "馬場 一郎"]);
+    }
+}
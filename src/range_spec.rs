@@ -0,0 +1,118 @@
+use std::ops::RangeInclusive;
+use thiserror::Error;
+
+// "0..=3,2..=3,9..=13" のような範囲指定を解釈する。
+// 各要素は "a..=b", "a..b", "..=b", "..b", "a" (1要素) のいずれか。空白は無視する。
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RangeSpecError {
+    #[error("range spec item #{index} is empty")]
+    Empty { index: usize },
+    #[error("range spec item #{index} ({item:?}) is not a range")]
+    Invalid { index: usize, item: String },
+    #[error("range spec item #{index} ({item:?}) ends at 0 and selects nothing")]
+    EndsAtZero { index: usize, item: String },
+    #[error("range spec item #{index} ({item:?}) ends where it starts and selects nothing")]
+    EmptyRange { index: usize, item: String },
+}
+
+// 範囲をデータに当てはめられなかった理由
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SliceError {
+    #[error("range {range:?} is past the end of data (len: {len})")]
+    PastEnd { range: RangeInclusive<usize>, len: usize },
+    #[error("range {range:?} is inverted")]
+    Inverted { range: RangeInclusive<usize> },
+    #[error("view was created at generation {created} but the buffer is now at generation {current}")]
+    Stale { created: u64, current: u64 },
+    #[error("offset {offset} is not on a char boundary")]
+    NotCharBoundary { offset: usize },
+    #[error("offset {offset} is not on a grapheme boundary")]
+    NotGraphemeBoundary { offset: usize },
+}
+
+pub fn parse_range_spec(spec: &str) -> Result<Vec<RangeInclusive<usize>>, RangeSpecError> {
+    if spec.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    spec.split(',')
+        .enumerate()
+        .map(|(index, item)| parse_item(index, item.trim()))
+        .collect()
+}
+
+fn parse_item(index: usize, item: &str) -> Result<RangeInclusive<usize>, RangeSpecError> {
+    if item.is_empty() {
+        return Err(RangeSpecError::Empty { index });
+    }
+    let invalid = || RangeSpecError::Invalid { index, item: item.to_owned() };
+    let num = |s: &str| -> Result<usize, RangeSpecError> {
+        s.trim().parse::<usize>().map_err(|_| invalid())
+    };
+    let start = |s: &str| if s.trim().is_empty() { Ok(0) } else { num(s) };
+
+    if let Some((a, b)) = item.split_once("..=") {
+        Ok(start(a)?..=num(b)?)
+    } else if let Some((a, b)) = item.split_once("..") {
+        let end = num(b)?;
+        if end == 0 {
+            return Err(RangeSpecError::EndsAtZero { index, item: item.to_owned() });
+        }
+        let start = start(a)?;
+        // "3..3" を 3..=2 にすると逆転した範囲と区別できないので、ここで弾く
+        if end == start {
+            return Err(RangeSpecError::EmptyRange { index, item: item.to_owned() });
+        }
+        Ok(start..=end - 1)
+    } else {
+        let v = num(item)?;
+        Ok(v..=v)
+    }
+}
+
+// dataから範囲を切り出す。data.getと違って、逆転した範囲も失敗として扱う
+pub fn resolve<'a, T>(data: &'a [T], range: &RangeInclusive<usize>) -> Result<&'a [T], SliceError> {
+    if range.start() > range.end() {
+        Err(SliceError::Inverted { range: range.clone() })
+    } else {
+        data.get(range.clone())
+            .ok_or_else(|| SliceError::PastEnd { range: range.clone(), len: data.len() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::range_spec::*;
+
+    #[test]
+    // 目的：各種の範囲指定が解釈できるかを確認する
+    fn test_parse_range_spec() {
+        assert_eq!(parse_range_spec("0..=3, 2..=3,9..=13").unwrap(), vec![0..=3, 2..=3, 9..=13]);
+        assert_eq!(parse_range_spec("..=3,..2,4..6,7").unwrap(), vec![0..=3, 0..=1, 4..=5, 7..=7]);
+        assert_eq!(parse_range_spec(" ").unwrap(), vec![]);
+    }
+
+    #[test]
+    // 目的：不正な範囲指定がどの要素かを示すエラーになるかを確認する
+    fn test_parse_range_spec_error() {
+        assert_eq!(parse_range_spec("0..=3,,1"), Err(RangeSpecError::Empty { index: 1 }));
+        assert_eq!(parse_range_spec("0..=x"),
+            Err(RangeSpecError::Invalid { index: 0, item: "0..=x".to_owned() }));
+        assert_eq!(parse_range_spec("1,..0"),
+            Err(RangeSpecError::EndsAtZero { index: 1, item: "..0".to_owned() }));
+        assert_eq!(parse_range_spec("1,3..3"),
+            Err(RangeSpecError::EmptyRange { index: 1, item: "3..3".to_owned() }));
+        // 逆転した範囲は解釈できるが、当てはめるときに失敗する
+        assert_eq!(parse_range_spec("5..3").unwrap(), vec![RangeInclusive::new(5, 2)]);
+    }
+
+    #[test]
+    // 目的：範囲外・逆転した範囲が理由付きで失敗するかを確認する
+    fn test_resolve() {
+        let data = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+        assert_eq!(resolve(&data, &(2..=3)), Ok(&[3, 4][..]));
+        assert_eq!(resolve(&data, &(9..=13)), Err(SliceError::PastEnd { range: 9..=13, len: 9 }));
+        let inverted = RangeInclusive::new(5, 4);
+        assert_eq!(resolve(&data, &inverted), Err(SliceError::Inverted { range: inverted.clone() }));
+    }
+}
@@ -0,0 +1,264 @@
+use std::ops::RangeInclusive;
+
+use crate::range_spec::{self, RangeSpecError, SliceError};
+
+// SliceHolder/SliceArrayの文字列版。
+// 範囲はバイトオフセットで持つが、文字(または書記素)の途中で切れる範囲はエラーにする。
+// "林竜太" のようなマルチバイト文字に 2..=3 を当てはめると文字が割れてしまうため。
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Boundary {
+    // charの境界
+    Char,
+    // 書記素(見た目の1文字)の境界。結合文字・異体字セレクタ・ZWJ・国旗などを簡易的に扱う
+    Grapheme,
+}
+
+#[derive(Debug)]
+pub struct StrSliceHolder<'a> {
+    s: Option<&'a str>,
+    range: RangeInclusive<usize>,
+    err: Option<SliceError>,
+}
+
+impl<'a> StrSliceHolder<'a> {
+    pub fn resolve(dat: &'a str, range: RangeInclusive<usize>, boundary: Boundary) -> StrSliceHolder<'a> {
+        let res = resolve(dat, &range, boundary);
+        StrSliceHolder::with_result(range, res)
+    }
+
+    fn with_result(range: RangeInclusive<usize>, res: Result<&'a str, SliceError>) -> StrSliceHolder<'a> {
+        match res {
+            Ok(s) => StrSliceHolder { s: Some(s), range, err: None },
+            Err(e) => StrSliceHolder { s: None, range, err: Some(e) },
+        }
+    }
+
+    pub fn get(&self) -> Option<&'a str> {
+        self.s
+    }
+
+    pub fn range(&self) -> &RangeInclusive<usize> {
+        &self.range
+    }
+
+    pub fn err(&self) -> Option<&SliceError> {
+        self.err.as_ref()
+    }
+}
+
+#[derive(Debug)]
+pub struct StrSliceArray<'a> {
+    dat: &'a str,
+    ss: Vec<StrSliceHolder<'a>>,
+}
+
+impl<'a> StrSliceArray<'a> {
+    // バイトオフセットの範囲で作る
+    pub fn with_ranges(dat: &'a str, ranges: Vec<RangeInclusive<usize>>, boundary: Boundary) -> Self {
+        StrSliceArray {
+            dat,
+            ss: ranges.into_iter().map(|r| StrSliceHolder::resolve(dat, r, boundary)).collect(),
+        }
+    }
+
+    pub fn from_spec(dat: &'a str, spec: &str, boundary: Boundary) -> Result<Self, RangeSpecError> {
+        Ok(StrSliceArray::with_ranges(dat, range_spec::parse_range_spec(spec)?, boundary))
+    }
+
+    // 文字の添字の範囲で作る。文字数を超える範囲はPastEnd (lenは文字数)。
+    // holderの範囲とエラーのオフセットも文字の添字で持つ
+    pub fn with_char_ranges(dat: &'a str, ranges: Vec<RangeInclusive<usize>>, boundary: Boundary) -> Self {
+        let chars = dat.chars().count();
+        let ss = ranges.into_iter()
+            .map(|r| {
+                let bytes = char_to_byte(dat, *r.start())
+                    .zip(r.end().checked_add(1).and_then(|end| char_to_byte(dat, end)))
+                    .filter(|_| r.start() <= r.end());
+                match bytes {
+                    Some((a, b)) => {
+                        let res = resolve(dat, &(a..=b - 1), boundary).map_err(|e| to_char_error(dat, e));
+                        StrSliceHolder::with_result(r, res)
+                    }
+                    None if r.start() > r.end() => StrSliceHolder::resolve(dat, r, boundary),
+                    None => StrSliceHolder {
+                        s: None,
+                        range: r.clone(),
+                        err: Some(SliceError::PastEnd { range: r, len: chars }),
+                    },
+                }
+            })
+            .collect();
+        StrSliceArray { dat, ss }
+    }
+
+    pub fn data(&self) -> &'a str {
+        self.dat
+    }
+
+    pub fn get(&self, idx: usize) -> Option<&'a str> {
+        self.ss.get(idx)?.get()
+    }
+
+    pub fn holders(&self) -> &[StrSliceHolder<'a>] {
+        &self.ss
+    }
+
+    // 切り出せなかった範囲の添字と理由
+    pub fn diagnostics(&self) -> impl Iterator<Item = (usize, &SliceError)> {
+        self.ss.iter().enumerate().filter_map(|(i, v)| v.err().map(|e| (i, e)))
+    }
+}
+
+// バイト範囲(終端を含む)を文字列から切り出す。両端が境界に乗っていなければエラー
+pub fn resolve<'a>(dat: &'a str, range: &RangeInclusive<usize>, boundary: Boundary) -> Result<&'a str, SliceError> {
+    range_spec::resolve(dat.as_bytes(), range)?;
+    let past_end = || SliceError::PastEnd { range: range.clone(), len: dat.len() };
+    let (a, b) = (*range.start(), range.end().checked_add(1).ok_or_else(past_end)?);
+    for offset in [a, b] {
+        if !dat.is_char_boundary(offset) {
+            return Err(SliceError::NotCharBoundary { offset });
+        }
+        if boundary == Boundary::Grapheme && !is_grapheme_boundary(dat, offset) {
+            return Err(SliceError::NotGraphemeBoundary { offset });
+        }
+    }
+    Ok(&dat[a..b])
+}
+
+// resolveのエラーのバイトオフセットを文字の添字に直す
+fn to_char_error(dat: &str, e: SliceError) -> SliceError {
+    let char_idx = |offset| byte_to_char(dat, offset).unwrap_or(offset);
+    match e {
+        SliceError::NotCharBoundary { offset } => SliceError::NotCharBoundary { offset: char_idx(offset) },
+        SliceError::NotGraphemeBoundary { offset } => SliceError::NotGraphemeBoundary { offset: char_idx(offset) },
+        e => e,
+    }
+}
+
+// 文字の添字 → バイトオフセット。文字数ちょうどの場合は末尾(=len)を返す
+pub fn char_to_byte(dat: &str, char_idx: usize) -> Option<usize> {
+    dat.char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(dat.len()))
+        .nth(char_idx)
+}
+
+// バイトオフセット → 文字の添字。文字の途中ならNone
+pub fn byte_to_char(dat: &str, offset: usize) -> Option<usize> {
+    if offset > dat.len() || !dat.is_char_boundary(offset) {
+        None
+    } else {
+        Some(dat[..offset].chars().count())
+    }
+}
+
+// 簡易的な書記素境界の判定。以下の直前では切らない:
+//   結合文字・濁点(U+3099/309A)・異体字セレクタ・絵文字の肌色修飾・ZWJ・タグ文字、ZWJの直後、
+//   CRLFの間、ペアになる国旗(Regional Indicator)の間
+pub fn is_grapheme_boundary(dat: &str, offset: usize) -> bool {
+    if offset == 0 || offset >= dat.len() {
+        return offset <= dat.len();
+    }
+    if !dat.is_char_boundary(offset) {
+        return false;
+    }
+    let (Some(prev), Some(next)) = (dat[..offset].chars().next_back(), dat[offset..].chars().next()) else {
+        return true;
+    };
+    if prev == '\r' && next == '\n' {
+        return false;
+    }
+    if prev == '\u{200D}' || is_extend(next) {
+        return false;
+    }
+    if is_regional_indicator(prev) && is_regional_indicator(next) {
+        // 直前に連続するRIが奇数個ならペアの途中
+        let n = dat[..offset].chars().rev().take_while(|c| is_regional_indicator(*c)).count();
+        return n % 2 == 0;
+    }
+    true
+}
+
+fn is_extend(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{3099}'..='\u{309A}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{E0100}'..='\u{E01EF}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{200D}'
+        | '\u{E0020}'..='\u{E007F}')
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    ('\u{1F1E6}'..='\u{1F1FF}').contains(&c)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::str_slice::*;
+
+    #[test]
+    // 目的：文字の途中で切れる範囲がエラーになるかを確認する
+    fn test_str_slice_char_boundary() {
+        let dat = "林竜太";
+        let sa = StrSliceArray::from_spec(dat, "0..=2,2..=3,3..=8,6..=10", Boundary::Char).unwrap();
+        assert_eq!(sa.get(0), Some("林"));
+        assert_eq!(sa.get(1), None);
+        assert_eq!(sa.get(2), Some("竜太"));
+        let diag: Vec<_> = sa.diagnostics().collect();
+        assert_eq!(diag, vec![
+            (1, &SliceError::NotCharBoundary { offset: 2 }),
+            (3, &SliceError::PastEnd { range: 6..=10, len: 9 }),
+        ]);
+    }
+
+    #[test]
+    // 目的：書記素境界を指定すると結合文字や国旗の途中で切れないかを確認する
+    fn test_str_slice_grapheme_boundary() {
+        // "か" + 結合用濁点
+        let dat = "か\u{3099}き";
+        assert_eq!(resolve(dat, &(0..=2), Boundary::Char), Ok("か"));
+        assert_eq!(resolve(dat, &(0..=2), Boundary::Grapheme),
+            Err(SliceError::NotGraphemeBoundary { offset: 3 }));
+        assert_eq!(resolve(dat, &(0..=5), Boundary::Grapheme), Ok("か\u{3099}"));
+
+        let flags = "\u{1F1EF}\u{1F1F5}\u{1F1FA}\u{1F1F8}";
+        assert!(is_grapheme_boundary(flags, 8));
+        assert!(!is_grapheme_boundary(flags, 4));
+        assert!(!is_grapheme_boundary(flags, 12));
+    }
+
+    #[test]
+    // 目的：バイトオフセットと文字の添字を相互に変換できるかを確認する
+    fn test_str_slice_offset_conversion() {
+        let dat = "Ryuta 林";
+        assert_eq!(char_to_byte(dat, 6), Some(6));
+        assert_eq!(char_to_byte(dat, 7), Some(9));
+        assert_eq!(char_to_byte(dat, 8), None);
+        assert_eq!(byte_to_char(dat, 9), Some(7));
+        assert_eq!(byte_to_char(dat, 7), None);
+
+        let sa = StrSliceArray::with_char_ranges("林竜太", vec![1..=2, 2..=3], Boundary::Char);
+        assert_eq!(sa.get(0), Some("竜太"));
+        assert_eq!(sa.holders()[1].err(), Some(&SliceError::PastEnd { range: 2..=3, len: 3 }));
+
+        // 終端がusize::MAXでも溢れずにPastEndになる
+        let sa = StrSliceArray::with_char_ranges("林竜太", vec![1..=usize::MAX], Boundary::Char);
+        assert_eq!(sa.holders()[0].err(), Some(&SliceError::PastEnd { range: 1..=usize::MAX, len: 3 }));
+        assert_eq!(resolve(dat, &(0..=usize::MAX), Boundary::Char),
+            Err(SliceError::PastEnd { range: 0..=usize::MAX, len: dat.len() }));
+
+        // 文字の添字で作った場合は、範囲もエラーのオフセットも文字の添字 (バイトなら3)
+        let sa = StrSliceArray::with_char_ranges("か\u{3099}き", vec![0..=0, 0..=1], Boundary::Grapheme);
+        assert_eq!(sa.holders()[0].range(), &(0..=0));
+        assert_eq!(sa.holders()[0].err(), Some(&SliceError::NotGraphemeBoundary { offset: 1 }));
+        assert_eq!(sa.holders()[1].range(), &(0..=1));
+        assert_eq!(sa.get(1), Some("か\u{3099}"));
+    }
+}
@@ -1,15 +1,31 @@
 use std::{io::Read};
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+
+pub mod edit;
+pub mod ring;
+pub mod stats;
+pub mod watch;
 
 struct PicoEntry<'a, T> {
     dat: Vec<&'a [T]>,
 }
 
-#[derive(Debug)]
 pub struct PicoTts<'a, T: 'static> {
     sys: Vec<T>,
     sub: Vec<&'a [T]>,
     subsub: Option<Vec<&'a [T]>>,
+    history: edit::PicoHistory<'a, T>,
+}
+
+// undoの履歴はsysの複製の山なので出さない
+impl<'a, T: std::fmt::Debug> std::fmt::Debug for PicoTts<'a, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PicoTts")
+            .field("sys", &self.sys)
+            .field("sub", &self.sub)
+            .field("subsub", &self.subsub)
+            .finish_non_exhaustive()
+    }
 }
 
 impl<'a, T> PicoTts<'a, T>
@@ -20,6 +36,7 @@ impl<'a, T> PicoTts<'a, T>
             sys: vec![],
             sub: Vec::new(),
             subsub: None,
+            history: edit::PicoHistory::new(),
         };
         x
     }
@@ -31,6 +48,7 @@ impl<'a, T> PicoTts<'a, T>
             sys: vec![1, 2, 3, 4, 5],
             sub: Vec::new(),
             subsub: None,
+            history: edit::PicoHistory::new(),
         };
         for i in 0..(x.sys.len() - 1) {
             // unsafe below.
@@ -45,27 +63,66 @@ impl<'a, T> PicoTts<'a, T>
             sys: Vec::from(dat),
             sub: Vec::new(),
             subsub: None,
+            history: edit::PicoHistory::new(),
         };
-        for i in 0..(x.sys.len() - 1) {
+        // 空データでもパニックしないようにする (ファイル監視で空ファイルが来ることがある)
+        for i in 0..x.sys.len().saturating_sub(1) {
             // unsafe below.
             // main.rsでやってるのと同様、anonymousなポインタを介す事でライフタイムを再設定する
             x.sub.push(unsafe { &*(x.sys[i..=i+1].as_ref() as *const _) });
         }
-        x.subsub = Some(Vec::from(unsafe { &*(x.sub.as_ref() as *const _) }));
+        x.subsub = Some(Vec::from(unsafe { &*(x.sub.as_slice() as *const [&[T]]) }));
         x
     }
 
     pub fn from_path(path: &std::ffi::OsStr) -> Result<PicoTts<'_, u8>> {
-        let mut v = std::fs::File::open(path)?;
+        let v = std::fs::File::open(path)?;
+        PicoTts::<u8>::from_reader(v, None, None).context("unexpect read to file.")
+    }
+
+    // 任意のReadから読み込む。limitを超えるとエラー、progressには読み込み済みのバイト数が通知される。
+    pub fn from_reader<R: Read>(
+        mut reader: R,
+        limit: Option<usize>,
+        mut progress: Option<&mut dyn FnMut(usize)>,
+    ) -> Result<PicoTts<'a, u8>> {
         let mut buf = vec![];
-        let _ = v.read_to_end(&mut buf).context("unexpect read to file.")?;
+        let mut chunk = [0u8; 8192];
+        loop {
+            let n = match reader.read(&mut chunk) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e).context("unexpect read from reader."),
+            };
+            if let Some(limit) = limit {
+                if buf.len() + n > limit {
+                    bail!("input exceeds size limit ({} bytes).", limit);
+                }
+            }
+            buf.extend_from_slice(&chunk[..n]);
+            if let Some(f) = progress.as_mut() {
+                f(buf.len());
+            }
+        }
         Ok(PicoTts {
             sys: buf,
             sub: Vec::new(),
             subsub: None,
+            history: edit::PicoHistory::new(),
         })
     }
 
+    // 標準入力をすべて読み込む。パイプでデータを流し込む用。
+    pub fn from_stdin() -> Result<PicoTts<'a, u8>> {
+        PicoTts::<u8>::from_reader(std::io::stdin().lock(), None, None)
+    }
+
+    // メモリ上のバイト列から読み込む。ファイルを触らずにテストしたい時用。
+    pub fn from_bytes(dat: &[u8]) -> Result<PicoTts<'a, u8>> {
+        PicoTts::<u8>::from_reader(dat, None, None)
+    }
+
     pub fn init(self: &'a mut PicoTts<'a, T>) -> &Self {
 
         if self.sys.len() > 0 {
@@ -73,7 +130,7 @@ impl<'a, T> PicoTts<'a, T>
                 let v = self.sys[i..=i+1].as_ref();
                 self.sub.push(v);
             }
-            self.subsub = Some(Vec::from(unsafe { &*(self.sub.as_ref() as *const _) }));
+            self.subsub = Some(Vec::from(unsafe { &*(self.sub.as_slice() as *const [&[T]]) }));
         }
         self
     }
@@ -141,7 +198,8 @@ mod tests {
     fn test_pico_tts_from_path() {
         let path = std::ffi::OsStr::new("Cargo.toml");
         let pico_tts = PicoTts::<'_, u8>::from_path(&path).unwrap();
-        assert_eq!(pico_tts.sys.len(), 226);
+        let len = std::fs::metadata("Cargo.toml").unwrap().len() as usize;
+        assert_eq!(pico_tts.sys.len(), len);
         assert_eq!(pico_tts.sub.len(), 0);
         assert_eq!(pico_tts.subsub, None);
     }
@@ -185,4 +243,33 @@ mod tests {
         assert_eq!(pico_tts.sub.len(), 4);
         assert_eq!(pico_tts.subsub.as_ref().unwrap().len(), 4);
     }
-}
\ No newline at end of file
+
+    #[test]
+    // 目的：PicoTts::from_reader()関数が全データを読み込み、進捗を通知するかを確認する
+    fn test_pico_tts_from_reader() {
+        let data: Vec<u8> = (0..20000u32).map(|v| v as u8).collect();
+        let mut last = 0;
+        let mut progress = |n: usize| last = n;
+        let pico_tts = PicoTts::<u8>::from_reader(
+            std::io::Cursor::new(&data), None, Some(&mut progress)).unwrap();
+        assert_eq!(pico_tts.sys, data);
+        assert_eq!(last, data.len());
+        assert_eq!(pico_tts.sub.len(), 0);
+    }
+
+    #[test]
+    // 目的：PicoTts::from_reader()関数がサイズ上限を超えたらエラーになるかを確認する
+    fn test_pico_tts_from_reader_limit() {
+        let data = [0u8; 100];
+        assert!(PicoTts::<u8>::from_reader(&data[..], Some(100), None).is_ok());
+        assert!(PicoTts::<u8>::from_reader(&data[..], Some(99), None).is_err());
+    }
+
+    #[test]
+    // 目的：PicoTts::from_bytes()関数が正しく動作するかを確認する
+    fn test_pico_tts_from_bytes() {
+        let pico_tts = PicoTts::<u8>::from_bytes(b"hello").unwrap();
+        assert_eq!(pico_tts.sys, b"hello".to_vec());
+        assert_eq!(pico_tts.subsub, None);
+    }
+}
@@ -0,0 +1,292 @@
+use std::collections::VecDeque;
+use std::ops::Range;
+use anyhow::{bail, Result};
+
+use super::PicoTts;
+
+// sysの編集API (insert/delete/replace) とundo/redo。
+//
+// 編集のたびにsub/subsubのwindowを以下のポリシーで付け替える。
+// 編集範囲を[s, e)、差し込むデータ長をnとすると、各window [ws, we) は
+//   - 編集範囲より前の部分 [ws, min(we, s))
+//   - 編集範囲より後ろの部分 [max(ws, e), we) を n - (e - s) だけずらしたもの
+// に分けられる。
+//   - 両方残ればwindowは2つに分割される (windowの途中への挿入など)
+//   - 片方だけ残ればずれる/縮むだけ (編集範囲と無関係なwindowはこれ)
+//   - どちらも空ならwindowは捨てる (編集範囲に完全に含まれる場合)
+// windowの先頭ちょうどに挿入した場合、windowは挿入データの後ろに移動する。
+// update_subで渡したsys以外を指すwindowは編集の影響を受けないのでそのまま残す。
+// undoの履歴は編集ごとにsys全体を複製するので、新しいものからlimit個までしか持たない。
+
+// undoの履歴の既定の上限
+pub const DEFAULT_HISTORY_LIMIT: usize = 64;
+
+#[derive(Debug, Clone)]
+enum Window<'a, T> {
+    // sys内の範囲
+    Inner(Range<usize>),
+    // sysの外を指すslice
+    Foreign(&'a [T]),
+}
+
+#[derive(Debug, Clone)]
+struct Snapshot<'a, T> {
+    sys: Vec<T>,
+    sub: Vec<Window<'a, T>>,
+    subsub: Option<Vec<Window<'a, T>>>,
+}
+
+pub struct PicoHistory<'a, T> {
+    // 古いものが先頭
+    undo: VecDeque<Snapshot<'a, T>>,
+    redo: Vec<Snapshot<'a, T>>,
+    limit: usize,
+}
+
+impl<'a, T> PicoHistory<'a, T> {
+    pub fn new() -> Self {
+        Self::with_limit(DEFAULT_HISTORY_LIMIT)
+    }
+
+    pub fn with_limit(limit: usize) -> Self {
+        PicoHistory { undo: VecDeque::new(), redo: vec![], limit }
+    }
+
+    // 上限を超えたら古いものから捨てる
+    fn push_undo(&mut self, snap: Snapshot<'a, T>) {
+        self.undo.push_back(snap);
+        while self.undo.len() > self.limit {
+            self.undo.pop_front();
+        }
+    }
+}
+
+impl<'a, T> Default for PicoHistory<'a, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a, T> PicoTts<'a, T>
+    where T: 'static + Clone {
+
+    pub fn insert(&mut self, at: usize, dat: &[T]) -> Result<&mut Self> {
+        self.replace(at..at, dat)
+    }
+
+    pub fn delete(&mut self, range: Range<usize>) -> Result<&mut Self> {
+        self.replace(range, &[])
+    }
+
+    // sys[range]をdatで置き換える。編集前の状態はundo用に積まれ、redoの履歴は捨てられる。
+    pub fn replace(&mut self, range: Range<usize>, dat: &[T]) -> Result<&mut Self> {
+        if range.start > range.end || range.end > self.sys.len() {
+            bail!("edit range {:?} is out of sys (len: {}).", range, self.sys.len());
+        }
+        let before = self.snapshot();
+        let shift = |w: &Window<'a, T>| -> Vec<Window<'a, T>> {
+            shift_window(w, &range, dat.len())
+        };
+        let sub: Vec<_> = before.sub.iter().flat_map(shift).collect();
+        let subsub = before.subsub.as_ref()
+            .map(|v| v.iter().flat_map(shift).collect());
+
+        let mut sys = before.sys.clone();
+        sys.splice(range.clone(), dat.iter().cloned());
+        self.history.push_undo(before);
+        self.history.redo.clear();
+        self.restore(Snapshot { sys, sub, subsub });
+        Ok(self)
+    }
+
+    // undoできる編集の数の上限。今の履歴が上限より多ければ古いものから捨てる
+    pub fn set_history_limit(&mut self, limit: usize) {
+        self.history.limit = limit;
+        while self.history.undo.len() > limit {
+            self.history.undo.pop_front();
+        }
+        self.history.redo.truncate(limit);
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.history.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.history.redo.is_empty()
+    }
+
+    // 直前の編集を取り消す。取り消す編集がなければfalse
+    pub fn undo(&mut self) -> bool {
+        match self.history.undo.pop_back() {
+            Some(prev) => {
+                let cur = self.snapshot();
+                self.history.redo.push(cur);
+                self.restore(prev);
+                true
+            }
+            None => false
+        }
+    }
+
+    // undoで取り消した編集をやり直す。やり直す編集がなければfalse
+    pub fn redo(&mut self) -> bool {
+        match self.history.redo.pop() {
+            Some(next) => {
+                let cur = self.snapshot();
+                self.history.push_undo(cur);
+                self.restore(next);
+                true
+            }
+            None => false
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot<'a, T> {
+        Snapshot {
+            sys: self.sys.clone(),
+            sub: self.sub.iter().map(|v| self.to_window(v)).collect(),
+            subsub: self.subsub.as_ref()
+                .map(|v| v.iter().map(|v| self.to_window(v)).collect()),
+        }
+    }
+
+    fn restore(&mut self, snap: Snapshot<'a, T>) {
+        self.sys = snap.sys;
+        // sysを差し替えた後でwindowを貼り直す
+        self.sub = snap.sub.iter().map(|w| self.resolve(w)).collect();
+        self.subsub = snap.subsub
+            .map(|v| v.iter().map(|w| self.resolve(w)).collect());
+    }
+
+    fn to_window(&self, s: &'a [T]) -> Window<'a, T> {
+        let size = std::mem::size_of::<T>();
+        let base = self.sys.as_ptr() as usize;
+        let p = s.as_ptr() as usize;
+        let end = base + std::mem::size_of_val(self.sys.as_slice());
+        if size == 0 || p < base || p + std::mem::size_of_val(s) > end {
+            return Window::Foreign(s);
+        }
+        let start = (p - base) / size;
+        Window::Inner(start..start + s.len())
+    }
+
+    fn resolve(&self, w: &Window<'a, T>) -> &'a [T] {
+        match w {
+            // unsafe below.
+            // new_with_dataと同様、ポインタを介してsysへの参照のライフタイムを再設定する
+            Window::Inner(r) => unsafe { &*(&self.sys[r.clone()] as *const [T]) },
+            Window::Foreign(s) => s,
+        }
+    }
+}
+
+fn shift_window<'a, T>(w: &Window<'a, T>, edit: &Range<usize>, n: usize) -> Vec<Window<'a, T>> {
+    let r = match w {
+        Window::Inner(r) => r,
+        Window::Foreign(s) => return vec![Window::Foreign(s)],
+    };
+    let mut ret = vec![];
+    let head = r.start..r.end.min(edit.start);
+    if head.start < head.end {
+        ret.push(Window::Inner(head));
+    }
+    let tail = r.start.max(edit.end)..r.end;
+    if tail.start < tail.end {
+        // tail.start >= edit.end なので引き算はあふれない
+        let moved = |v: usize| v - edit.end + edit.start + n;
+        ret.push(Window::Inner(moved(tail.start)..moved(tail.end)));
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sub1::*;
+
+    #[test]
+    // 目的：windowの途中に挿入するとwindowが分割されるかを確認する
+    fn test_pico_tts_insert_split() {
+        let mut pico_tts = PicoTts::new_with_data(&[1, 2, 3]);
+        pico_tts.insert(1, &[10, 11]).unwrap();
+        assert_eq!(pico_tts.sys, vec![1, 10, 11, 2, 3]);
+        let test: Vec<&[i32]> = vec![&[1], &[2], &[2, 3]];
+        assert_eq!(pico_tts.sub, test);
+        assert_eq!(pico_tts.subsub.as_ref().unwrap(), &test);
+    }
+
+    #[test]
+    // 目的：削除範囲に含まれるwindowが捨てられ、後ろのwindowがずれるかを確認する
+    fn test_pico_tts_delete() {
+        let mut pico_tts = PicoTts::new_with_data(&[1, 2, 3, 4, 5]);
+        pico_tts.delete(1..3).unwrap();
+        assert_eq!(pico_tts.sys, vec![1, 4, 5]);
+        let test: Vec<&[i32]> = vec![&[1], &[4], &[4, 5]];
+        assert_eq!(pico_tts.sub, test);
+    }
+
+    #[test]
+    // 目的：範囲外の編集がエラーになり、状態が変わらないかを確認する
+    fn test_pico_tts_edit_out_of_range() {
+        let mut pico_tts = PicoTts::new_with_data(&[1, 2, 3]);
+        assert!(pico_tts.replace(2..4, &[0]).is_err());
+        assert!(pico_tts.insert(4, &[0]).is_err());
+        assert_eq!(pico_tts.sys, vec![1, 2, 3]);
+        assert!(!pico_tts.can_undo());
+    }
+
+    #[test]
+    // 目的：undo/redoで編集前後の状態に戻れるかを確認する
+    fn test_pico_tts_undo_redo() {
+        let mut pico_tts = PicoTts::new_with_data(&[1, 2, 3, 4]);
+        pico_tts.replace(1..3, &[7, 8, 9]).unwrap();
+        pico_tts.delete(0..1).unwrap();
+        assert_eq!(pico_tts.sys, vec![7, 8, 9, 4]);
+
+        assert!(pico_tts.undo());
+        assert_eq!(pico_tts.sys, vec![1, 7, 8, 9, 4]);
+        assert!(pico_tts.undo());
+        assert_eq!(pico_tts.sys, vec![1, 2, 3, 4]);
+        let test: Vec<&[i32]> = vec![&[1, 2], &[2, 3], &[3, 4]];
+        assert_eq!(pico_tts.sub, test);
+        assert!(!pico_tts.undo());
+
+        assert!(pico_tts.redo());
+        assert_eq!(pico_tts.sys, vec![1, 7, 8, 9, 4]);
+        // 新しい編集をするとredoの履歴は消える
+        pico_tts.insert(0, &[0]).unwrap();
+        assert!(!pico_tts.can_redo());
+    }
+
+    #[test]
+    // 目的：undoの履歴が上限を超えると古いものから捨てられ、Debugに履歴が出ないかを確認する
+    fn test_pico_tts_history_limit() {
+        let mut pico_tts = PicoTts::new_with_data(&[1, 2, 3]);
+        pico_tts.set_history_limit(2);
+        for i in 0..3 {
+            pico_tts.insert(0, &[10 + i]).unwrap();
+        }
+        assert_eq!(pico_tts.sys, vec![12, 11, 10, 1, 2, 3]);
+        assert!(pico_tts.undo());
+        assert!(pico_tts.undo());
+        assert!(!pico_tts.undo());
+        assert_eq!(pico_tts.sys, vec![10, 1, 2, 3]);
+        assert!(!format!("{:?}", pico_tts).contains("history"));
+
+        pico_tts.set_history_limit(0);
+        pico_tts.insert(0, &[0]).unwrap();
+        assert!(!pico_tts.can_undo() && !pico_tts.can_redo());
+    }
+
+    #[test]
+    // 目的：update_subで渡したsys外のwindowが編集で変化しないかを確認する
+    fn test_pico_tts_edit_foreign_window() {
+        let mut pico_tts = PicoTts::new_with_data(&[1, 2, 3]);
+        let dat: Vec<&[i32]> = vec![&[11, 12]];
+        pico_tts.update_sub(dat);
+        pico_tts.delete(0..3).unwrap();
+        assert_eq!(pico_tts.sub.len(), 0);
+        let test: Vec<&[i32]> = vec![&[11, 12]];
+        assert_eq!(pico_tts.subsub.as_ref().unwrap(), &test);
+    }
+}
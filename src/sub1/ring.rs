@@ -0,0 +1,192 @@
+use std::ops::Range;
+
+// 固定容量の循環バッファを持つPicoTts。容量を超えると古いデータから上書きされる。
+// windowは論理位置 (作成してからpushした通し番号) の範囲で持ち、
+// 物理的な末尾をまたぐ場合は2つのsliceに分かれて返る。
+#[derive(Debug)]
+pub struct PicoTtsRing<T> {
+    sys: Vec<T>,
+    cap: usize,
+    // 最古のデータがある物理位置
+    start: usize,
+    // これまでにpushした数 = 次に書き込む論理位置
+    written: usize,
+    sub: Vec<Range<usize>>,
+}
+
+// 循環バッファ上のwindow。末尾をまたがなければtailは空
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RingSlice<'a, T> {
+    head: &'a [T],
+    tail: &'a [T],
+}
+
+impl<'a, T> RingSlice<'a, T> {
+    pub fn as_slices(&self) -> (&'a [T], &'a [T]) {
+        (self.head, self.tail)
+    }
+
+    pub fn len(&self) -> usize {
+        self.head.len() + self.tail.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn is_contiguous(&self) -> bool {
+        self.tail.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &'a T> {
+        self.head.iter().chain(self.tail.iter())
+    }
+
+    pub fn to_vec(&self) -> Vec<T>
+        where T: Clone
+    {
+        self.iter().cloned().collect()
+    }
+}
+
+impl<T> PicoTtsRing<T>
+    where T: Clone {
+
+    // capacityが0の場合はパニックする
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "PicoTtsRing capacity must be positive.");
+        PicoTtsRing {
+            sys: Vec::with_capacity(capacity),
+            cap: capacity,
+            start: 0,
+            written: 0,
+            sub: Vec::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cap
+    }
+
+    pub fn len(&self) -> usize {
+        self.sys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sys.is_empty()
+    }
+
+    // 保持している最古のデータの論理位置
+    pub fn oldest(&self) -> usize {
+        self.written - self.sys.len()
+    }
+
+    // 次に書き込まれる論理位置
+    pub fn end(&self) -> usize {
+        self.written
+    }
+
+    pub fn push(&mut self, v: T) {
+        if self.sys.len() < self.cap {
+            self.sys.push(v);
+        } else {
+            self.sys[self.start] = v;
+            self.start = (self.start + 1) % self.cap;
+        }
+        self.written += 1;
+    }
+
+    pub fn extend_from_slice(&mut self, dat: &[T]) {
+        dat.iter().cloned().for_each(|v| self.push(v));
+    }
+
+    // 論理位置の範囲でwindowを取り出す。上書き済み・未書き込みの位置を含む場合はNone
+    pub fn window(&self, range: Range<usize>) -> Option<RingSlice<'_, T>> {
+        if range.start > range.end || range.start < self.oldest() || range.end > self.written {
+            return None;
+        }
+        let n = range.end - range.start;
+        let a = (self.start + (range.start - self.oldest())) % self.cap;
+        if a + n <= self.sys.len() {
+            Some(RingSlice { head: &self.sys[a..a + n], tail: &[] })
+        } else {
+            let head = &self.sys[a..];
+            Some(RingSlice { head, tail: &self.sys[..n - head.len()] })
+        }
+    }
+
+    // PicoTts::initと同様、今あるデータに隣り合う2要素のwindowを登録する
+    pub fn init(&mut self) -> &mut Self {
+        for i in self.oldest()..self.end().saturating_sub(1) {
+            self.sub.push(i..i + 2);
+        }
+        self
+    }
+
+    pub fn add_sub(&mut self, range: Range<usize>) -> &mut Self {
+        self.sub.push(range);
+        self
+    }
+
+    // 登録したwindowの論理位置
+    pub fn sub_range(&self, idx: usize) -> Option<Range<usize>> {
+        self.sub.get(idx).cloned()
+    }
+
+    // 登録したwindowを取り出す。上書きされて無効になっていればNone
+    pub fn get_sub(&self, idx: usize) -> Option<RingSlice<'_, T>> {
+        self.window(self.sub.get(idx)?.clone())
+    }
+
+    // 上書きされて参照できなくなったwindowを取り除く
+    pub fn prune_sub(&mut self) -> &mut Self {
+        let oldest = self.oldest();
+        self.sub.retain(|r| r.start >= oldest);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sub1::ring::*;
+
+    #[test]
+    // 目的：容量を超えたら古いデータから上書きされるかを確認する
+    fn test_pico_tts_ring_overwrite() {
+        let mut ring = PicoTtsRing::new(4);
+        ring.extend_from_slice(&[1, 2, 3]);
+        assert_eq!(ring.window(0..3).unwrap().as_slices(), (&[1, 2, 3][..], &[][..]));
+        ring.extend_from_slice(&[4, 5, 6]);
+        assert_eq!(ring.len(), 4);
+        assert_eq!(ring.oldest(), 2);
+        assert_eq!(ring.end(), 6);
+        assert_eq!(ring.window(2..6).unwrap().to_vec(), vec![3, 4, 5, 6]);
+        assert_eq!(ring.window(1..3), None);
+        assert_eq!(ring.window(5..7), None);
+    }
+
+    #[test]
+    // 目的：物理的な末尾をまたぐwindowが2つのsliceで返るかを確認する
+    fn test_pico_tts_ring_wrap() {
+        let mut ring = PicoTtsRing::new(4);
+        ring.extend_from_slice(&[1, 2, 3, 4, 5]);
+        let w = ring.window(2..5).unwrap();
+        assert!(!w.is_contiguous());
+        assert_eq!(w.as_slices(), (&[3, 4][..], &[5][..]));
+        assert_eq!(w.len(), 3);
+    }
+
+    #[test]
+    // 目的：登録したwindowが上書き後に無効になるかを確認する
+    fn test_pico_tts_ring_sub() {
+        let mut ring = PicoTtsRing::new(3);
+        ring.extend_from_slice(&[1, 2, 3]);
+        ring.init();
+        assert_eq!(ring.get_sub(1).unwrap().to_vec(), vec![2, 3]);
+        ring.push(4);
+        assert_eq!(ring.get_sub(0), None);
+        assert_eq!(ring.get_sub(1).unwrap().to_vec(), vec![2, 3]);
+        ring.prune_sub();
+        assert_eq!(ring.sub_range(0), Some(1..3));
+    }
+}
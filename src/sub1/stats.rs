@@ -0,0 +1,165 @@
+use std::iter::Sum;
+
+use super::PicoTts;
+
+// 数値型のPicoTtsに対するwindow(sub)ごとの集計。
+// 結果のVecはget_subと同じ添字で引ける。initで作るペアのwindowに使えば移動平均などになる。
+// 平均・分散・中央値はf64で計算する。i64/u64などInto<f64>の無い型も扱えるようにToF64で変換する。
+
+// f64への変換。64ビット以上の整数は丸められることがある
+pub trait ToF64: Copy {
+    fn to_f64(self) -> f64;
+}
+
+macro_rules! impl_to_f64 {
+    ($($t:ty),*) => {
+        $(impl ToF64 for $t {
+            fn to_f64(self) -> f64 {
+                self as f64
+            }
+        })*
+    };
+}
+
+impl_to_f64!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize, f32, f64);
+
+impl<'a, T> PicoTts<'a, T>
+    where T: 'static + Clone {
+
+    // 任意の畳み込み
+    pub fn sub_fold<B, F>(&self, init: B, mut f: F) -> Vec<B>
+        where B: Clone, F: FnMut(B, &T) -> B
+    {
+        self.sub.iter()
+            .map(|w| w.iter().fold(init.clone(), &mut f))
+            .collect()
+    }
+
+    // Sで足す。u8のwindowをu8のまま足すと溢れるので、Sには十分広い型を選ぶ (sub_sum::<u64>() など)
+    pub fn sub_sum<S>(&self) -> Vec<S>
+        where T: Copy + Into<S>, S: Sum<S>
+    {
+        self.sub.iter().map(|w| w.iter().map(|v| (*v).into()).sum()).collect()
+    }
+
+    pub fn sub_min(&self) -> Vec<Option<T>>
+        where T: Copy + PartialOrd
+    {
+        self.sub.iter()
+            .map(|w| w.iter().copied().reduce(|m, v| if v < m { v } else { m }))
+            .collect()
+    }
+
+    pub fn sub_max(&self) -> Vec<Option<T>>
+        where T: Copy + PartialOrd
+    {
+        self.sub.iter()
+            .map(|w| w.iter().copied().reduce(|m, v| if v > m { v } else { m }))
+            .collect()
+    }
+
+    // 空のwindowはNone
+    pub fn sub_mean(&self) -> Vec<Option<f64>>
+        where T: ToF64
+    {
+        self.sub.iter().map(|w| mean(w)).collect()
+    }
+
+    // 母分散 (nで割る)。空のwindowはNone
+    pub fn sub_variance(&self) -> Vec<Option<f64>>
+        where T: ToF64
+    {
+        self.sub.iter()
+            .map(|w| {
+                let m = mean(w)?;
+                let sq: f64 = w.iter().map(|v| (v.to_f64() - m).powi(2)).sum();
+                Some(sq / w.len() as f64)
+            })
+            .collect()
+    }
+
+    // 要素数が偶数の場合は中央2つの平均。空のwindowはNone
+    pub fn sub_median(&self) -> Vec<Option<f64>>
+        where T: ToF64
+    {
+        self.sub.iter()
+            .map(|w| {
+                if w.is_empty() {
+                    return None;
+                }
+                let mut v: Vec<f64> = w.iter().map(|v| v.to_f64()).collect();
+                v.sort_by(|a, b| a.total_cmp(b));
+                let mid = v.len() / 2;
+                if v.len().is_multiple_of(2) {
+                    Some((v[mid - 1] + v[mid]) / 2.0)
+                } else {
+                    Some(v[mid])
+                }
+            })
+            .collect()
+    }
+}
+
+fn mean<T: ToF64>(w: &[T]) -> Option<f64> {
+    if w.is_empty() {
+        None
+    } else {
+        let sum: f64 = w.iter().map(|v| v.to_f64()).sum();
+        Some(sum / w.len() as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::sub1::*;
+
+    #[test]
+    // 目的：ペアのwindowに対する合計・平均・最小・最大が正しいかを確認する
+    fn test_pico_tts_sub_basic_stats() {
+        let pico_tts = PicoTts::new_with_data(&[1, 3, 2, 6]);
+        assert_eq!(pico_tts.sub_sum::<i32>(), vec![4, 5, 8]);
+        assert_eq!(pico_tts.sub_mean(), vec![Some(2.0), Some(2.5), Some(4.0)]);
+        assert_eq!(pico_tts.sub_min(), vec![Some(1), Some(2), Some(2)]);
+        assert_eq!(pico_tts.sub_max(), vec![Some(3), Some(3), Some(6)]);
+
+        // u8のwindowでも広い型で足せば溢れない
+        let pico_tts = PicoTts::new_with_data(&[200u8, 100, 255]);
+        assert_eq!(pico_tts.sub_sum::<u32>(), vec![300, 355]);
+        assert_eq!(pico_tts.sub_sum::<f64>(), vec![300.0, 355.0]);
+    }
+
+    #[test]
+    // 目的：分散・中央値・畳み込みが正しいかを確認する
+    fn test_pico_tts_sub_variance_median_fold() {
+        let mut pico_tts = PicoTts::new_with_data(&[2.0f32, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        let all: Vec<&[f32]> = vec![&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0], &[9.0, 1.0, 5.0]];
+        pico_tts.sub = all;
+        assert_eq!(pico_tts.sub_variance(), vec![Some(4.0), Some(32.0 / 3.0)]);
+        assert_eq!(pico_tts.sub_median(), vec![Some(4.5), Some(5.0)]);
+        assert_eq!(pico_tts.sub_fold(1.0f32, |acc, v| acc * v), vec![201600.0, 45.0]);
+    }
+
+    #[test]
+    // 目的：Into<f64>の無い64ビット整数のwindowでも平均・分散・中央値が求まるかを確認する
+    fn test_pico_tts_sub_stats_u64() {
+        let pico_tts = PicoTts::new_with_data(&[1u64 << 40, 3 << 40, 5 << 40]);
+        let x = (1u64 << 40) as f64;
+        assert_eq!(pico_tts.sub_mean(), vec![Some(2.0 * x), Some(4.0 * x)]);
+        assert_eq!(pico_tts.sub_variance(), vec![Some(x * x), Some(x * x)]);
+        assert_eq!(pico_tts.sub_median(), vec![Some(2.0 * x), Some(4.0 * x)]);
+        assert_eq!(pico_tts.sub_sum::<u64>(), vec![4 << 40, 8 << 40]);
+
+        let pico_tts = PicoTts::new_with_data(&[-3i64, 5, -1]);
+        assert_eq!(pico_tts.sub_mean()[0], Some(1.0));
+        let pico_tts = PicoTts::new_with_data(&[2usize, 4]);
+        assert_eq!(pico_tts.sub_median(), vec![Some(3.0)]);
+    }
+
+    #[test]
+    // 目的：windowがない場合は空の結果になるかを確認する
+    fn test_pico_tts_sub_stats_empty() {
+        let pico_tts: PicoTts<u8> = PicoTts::new();
+        assert!(pico_tts.sub_sum::<u8>().is_empty());
+        assert!(pico_tts.sub_median().is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    time::{Duration, SystemTime},
+};
+use anyhow::{Context, Result};
+
+use super::PicoTts;
+
+// 監視対象から作ったPicoTts。古いインスタンスを掴んでいる読み手は、dropするまでそのまま使える。
+pub type SharedPicoTts = Arc<PicoTts<'static, u8>>;
+
+type Subscriber = Box<dyn FnMut(&SharedPicoTts) + Send>;
+type ErrorHandler = Box<dyn FnMut(&anyhow::Error) + Send>;
+
+// mtimeの分解能の上限 (FATは2秒)。読み込んだ時刻からこれ以内のmtimeは、
+// 同じ長さで書き直されてもmtimeが変わらないことがあるので当てにしない
+const MTIME_GRANULARITY: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+    hash: u64,
+    // 読み込みを始めた時刻
+    loaded: SystemTime,
+}
+
+impl FileStamp {
+    // mtimeとsizeが同じなら中身も同じとみなせるか
+    fn trusted(&self) -> bool {
+        self.modified.is_some_and(|m| m + MTIME_GRANULARITY <= self.loaded)
+    }
+}
+
+// from_pathで読み込むファイルをポーリングし、変更されていればPicoTts(windowsも含む)を作り直す。
+// 変更判定はmtime/sizeを見て、変わっていれば中身のハッシュまで比較する。
+// 読み込んだ時刻に近いmtimeは当てにならないので、その間はmtime/sizeが同じでもハッシュを比較する。
+pub struct PicoWatcher {
+    path: PathBuf,
+    stamp: FileStamp,
+    current: SharedPicoTts,
+    subscribers: Vec<Subscriber>,
+    error_handlers: Vec<ErrorHandler>,
+}
+
+impl PicoWatcher {
+    pub fn new(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (stamp, current) = load(&path)?;
+        Ok(PicoWatcher {
+            path,
+            stamp,
+            current,
+            subscribers: vec![],
+            error_handlers: vec![],
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn current(&self) -> SharedPicoTts {
+        self.current.clone()
+    }
+
+    // 作り直しのたびに新しいインスタンスで呼ばれる
+    pub fn subscribe<F>(&mut self, f: F)
+        where F: FnMut(&SharedPicoTts) + Send + 'static
+    {
+        self.subscribers.push(Box::new(f));
+    }
+
+    // runの中のpollが失敗するたびに呼ばれる
+    pub fn on_error<F>(&mut self, f: F)
+        where F: FnMut(&anyhow::Error) + Send + 'static
+    {
+        self.error_handlers.push(Box::new(f));
+    }
+
+    // 変更があって作り直した場合はtrueを返す
+    pub fn poll(&mut self) -> Result<bool> {
+        let meta = std::fs::metadata(&self.path)
+            .with_context(|| format!("unexpect stat of {:?}.", self.path))?;
+        let modified = meta.modified().ok();
+        if modified == self.stamp.modified && meta.len() == self.stamp.len && self.stamp.trusted() {
+            return Ok(false);
+        }
+
+        let (stamp, pt) = load(&self.path)?;
+        if stamp.hash == self.stamp.hash && stamp.len == self.stamp.len {
+            // touchされただけ、または変わっていない
+            self.stamp = stamp;
+            return Ok(false);
+        }
+        self.stamp = stamp;
+        self.current = pt;
+        for f in self.subscribers.iter_mut() {
+            f(&self.current);
+        }
+        Ok(true)
+    }
+
+    // stopが立つまでintervalごとにpollする。別スレッドで回す想定。
+    // 保存中の置き換え (renameの途中) などで読めなかった回は、on_errorに渡して次の回に読み直す
+    pub fn run(&mut self, interval: Duration, stop: &AtomicBool) {
+        while !stop.load(Ordering::Relaxed) {
+            if let Err(e) = self.poll() {
+                for f in self.error_handlers.iter_mut() {
+                    f(&e);
+                }
+            }
+            std::thread::sleep(interval);
+        }
+    }
+}
+
+impl std::fmt::Debug for PicoWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PicoWatcher")
+            .field("path", &self.path)
+            .field("stamp", &self.stamp)
+            .field("current", &self.current)
+            .field("subscribers", &self.subscribers.len())
+            .field("error_handlers", &self.error_handlers.len())
+            .finish()
+    }
+}
+
+fn load(path: &Path) -> Result<(FileStamp, SharedPicoTts)> {
+    let loaded = SystemTime::now();
+    let meta = std::fs::metadata(path)
+        .with_context(|| format!("unexpect stat of {:?}.", path))?;
+    let pt = PicoTts::<u8>::from_path(path.as_os_str())?;
+    let mut hasher = DefaultHasher::new();
+    pt.sys.hash(&mut hasher);
+    let stamp = FileStamp {
+        modified: meta.modified().ok(),
+        len: pt.sys.len() as u64,
+        hash: hasher.finish(),
+        loaded,
+    };
+    Ok((stamp, Arc::new(PicoTts::new_with_data(&pt.sys))))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{atomic::AtomicUsize, Mutex};
+    use crate::sub1::watch::*;
+
+    fn temp_file(name: &str, dat: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir()
+            .join(format!("pico_watch_{}_{}", std::process::id(), name));
+        std::fs::write(&path, dat).unwrap();
+        path
+    }
+
+    #[test]
+    // 目的：ファイルが変更されたらPicoTtsが作り直され、購読者に通知されるかを確認する
+    fn test_pico_watcher_reload() {
+        let path = temp_file("reload", &[1, 2, 3]);
+        let mut w = PicoWatcher::new(&path).unwrap();
+        let old = w.current();
+        assert_eq!(old.get_sub(1), Some(&[2u8, 3][..]));
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        w.subscribe(move |pt| seen2.lock().unwrap().push(pt.sys.clone()));
+
+        assert!(!w.poll().unwrap());
+        std::fs::write(&path, [9, 8, 7, 6]).unwrap();
+        assert!(w.poll().unwrap());
+
+        // 古いインスタンスは有効なまま
+        assert_eq!(old.sys, vec![1, 2, 3]);
+        assert_eq!(w.current().sys, vec![9, 8, 7, 6]);
+        assert_eq!(w.current().sub.len(), 3);
+        assert_eq!(*seen.lock().unwrap(), vec![vec![9, 8, 7, 6]]);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    // 目的：中身が同じなら作り直さないことを確認する
+    fn test_pico_watcher_same_content() {
+        let path = temp_file("same", b"abc");
+        let mut w = PicoWatcher::new(&path).unwrap();
+        let count = Arc::new(AtomicUsize::new(0));
+        let count2 = count.clone();
+        w.subscribe(move |_| { count2.fetch_add(1, Ordering::SeqCst); });
+
+        std::fs::write(&path, b"abc").unwrap();
+        assert!(!w.poll().unwrap());
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    // 目的：mtimeとsizeが同じでも、読み込んだ直後の書き換えなら中身を比較して作り直すかを確認する
+    fn test_pico_watcher_same_size_rewrite() {
+        let path = temp_file("same_size", b"abc");
+        let mut w = PicoWatcher::new(&path).unwrap();
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        std::fs::write(&path, b"xyz").unwrap();
+        std::fs::File::options().write(true).open(&path).unwrap().set_modified(modified).unwrap();
+        assert!(w.poll().unwrap());
+        assert_eq!(w.current().sys, b"xyz".to_vec());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    // 目的：ファイルが一時的に無くなってもrunが止まらず、戻ったら作り直すかを確認する
+    fn test_pico_watcher_run_survives_errors() {
+        let path = temp_file("run", b"abc");
+        let mut w = PicoWatcher::new(&path).unwrap();
+        let seen = Arc::new(Mutex::new(vec![]));
+        let seen2 = seen.clone();
+        w.subscribe(move |pt| seen2.lock().unwrap().push(pt.sys.clone()));
+        let errors = Arc::new(AtomicUsize::new(0));
+        let errors2 = errors.clone();
+        w.on_error(move |e| {
+            assert!(format!("{:#}", e).contains("unexpect stat"));
+            errors2.fetch_add(1, Ordering::Relaxed);
+        });
+        std::fs::remove_file(&path).unwrap();
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let stop2 = stop.clone();
+        let h = std::thread::spawn(move || w.run(Duration::from_millis(1), &stop2));
+        std::thread::sleep(Duration::from_millis(20));
+        std::fs::write(&path, b"abcd").unwrap();
+        for _ in 0..1000 {
+            if !seen.lock().unwrap().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        stop.store(true, Ordering::Relaxed);
+        h.join().unwrap();
+        assert_eq!(*seen.lock().unwrap(), vec![b"abcd".to_vec()]);
+        // ファイルが無かった間の失敗はon_errorに渡っている
+        assert!(errors.load(Ordering::Relaxed) > 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+}